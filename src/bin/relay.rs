@@ -0,0 +1,21 @@
+use anyhow::Context;
+
+/// Address the relay listens on unless `--address` says otherwise
+const ADDRESS: &str = "0.0.0.0:9000";
+
+fn main() -> anyhow::Result<()> {
+    simple_logger::SimpleLogger::new()
+        .with_level(log::LevelFilter::Info)
+        .init()
+        .context("Couldn't initialise the logger")?;
+
+    let address = std::env::args()
+        .skip_while(|arg| arg != "--address")
+        .nth(1)
+        .unwrap_or_else(|| ADDRESS.to_string());
+    let listener = std::net::TcpListener::bind(&address)
+        .with_context(|| format!("Couldn't listen on {}", address))?;
+    log::info!("Relaying on ws://{}", address);
+
+    tet_rs::tetrs::relay::serve(listener)
+}
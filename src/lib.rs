@@ -1,4 +1,4 @@
-mod tetrs;
+pub mod tetrs;
 mod utils;
 
 #[cfg(target_arch = "wasm32")]
@@ -41,11 +41,45 @@ async fn execute() {
         })
         .expect("couldn't append canvas to document body");
 
-    let tetrs = tetrs::Tetrs::new(&window, &event_loop)
-        .await
-        .expect("Can't create tetrs");
+    let input_map = tetrs::input::InputMap::load().unwrap_or_else(|err| {
+        log::warn!("Using the default key bindings: {:?}", err);
+        tetrs::input::InputMap::default()
+    });
+
+    let tetrs = tetrs::Tetrs::new(
+        &window,
+        &event_loop,
+        tetrs::rules::Rules::default(),
+        input_map,
+    )
+    .await
+    .expect("Can't create tetrs");
+
+    // Online play with ?server=ws://host:port&room=name in the page's url
+    #[cfg(target_arch = "wasm32")]
+    let mut tetrs = tetrs;
+    #[cfg(target_arch = "wasm32")]
+    if let Some(url) = query("server") {
+        let room = query("room").unwrap_or_else(|| tetrs::online::DEFAULT_ROOM.to_string());
+        if let Err(err) = tetrs.connect(&url, &room) {
+            log::warn!("Couldn't play online: {:?}", err);
+        }
+    }
 
     tetrs::run(window, event_loop, tetrs)
         .await
         .expect("Couldn't run tetrs");
 }
+
+/// Value of a `name=value` parameter in the page's url
+#[cfg(target_arch = "wasm32")]
+fn query(name: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .and_then(|(_, value)| js_sys::decode_uri_component(value).ok())
+        .and_then(|value| value.as_string())
+}
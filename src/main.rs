@@ -1,6 +1,5 @@
-mod tetrs;
-
 use anyhow::Context;
+use tet_rs::tetrs;
 
 const WINDOW_WIDTH: u32 = 600;
 const WINDOW_HEIGHT: u32 = 600;
@@ -25,9 +24,93 @@ async fn execute() -> anyhow::Result<()> {
         .build(&event_loop)
         .context("Couldn't initialise the window")?;
 
-    let tetrs = tetrs::Tetrs::new(&window, &event_loop)
+    let defaults = tetrs::rules::Rules::default();
+    let rules = tetrs::rules::Rules {
+        mode: option("--mode")
+            .map(|name| name.parse())
+            .transpose()?
+            .unwrap_or(defaults.mode),
+        randomizer: option("--randomizer")
+            .map(|name| name.parse())
+            .transpose()?
+            .unwrap_or(defaults.randomizer),
+        preview_count: option("--preview")
+            .map(|count| count.parse())
+            .transpose()
+            .context("Number of previews should be a number")?
+            .unwrap_or(defaults.preview_count),
+        rotation: option("--rotation")
+            .map(|name| name.parse())
+            .transpose()?
+            .unwrap_or(defaults.rotation),
+        lock_delay: option("--lock-delay")
+            .map(|millis| millis.parse().map(std::time::Duration::from_millis))
+            .transpose()
+            .context("Lock delay should be in milliseconds")?
+            .unwrap_or(defaults.lock_delay),
+        scoring: option("--scoring")
+            .map(|name| name.parse())
+            .transpose()?
+            .unwrap_or(defaults.scoring),
+        handling: tetrs::controls::Handling {
+            das: option("--das")
+                .map(|millis| millis.parse().map(std::time::Duration::from_millis))
+                .transpose()
+                .context("DAS should be in milliseconds")?
+                .unwrap_or(defaults.handling.das),
+            arr: option("--arr")
+                .map(|millis| millis.parse().map(std::time::Duration::from_millis))
+                .transpose()
+                .context("ARR should be in milliseconds")?
+                .unwrap_or(defaults.handling.arr),
+            soft_drop_factor: option("--sdf")
+                .map(|factor| factor.parse())
+                .transpose()
+                .context("Soft drop factor should be a number")?
+                .unwrap_or(defaults.handling.soft_drop_factor),
+        },
+        garbage_rows: option("--garbage")
+            .map(|rows| rows.parse())
+            .transpose()
+            .context("Garbage rows should be a number")?
+            .unwrap_or(defaults.garbage_rows),
+        messy_garbage: std::env::args().any(|arg| arg == "--messy"),
+        garbage_delay: option("--garbage-delay")
+            .map(|millis| millis.parse().map(std::time::Duration::from_millis))
+            .transpose()
+            .context("Garbage delay should be in milliseconds")?
+            .unwrap_or(defaults.garbage_delay),
+        ..defaults
+    };
+    if !(1..=tetrs::mode::MAX_GARBAGE_ROWS).contains(&rules.garbage_rows) {
+        anyhow::bail!(
+            "Garbage rows should be from 1 to {}",
+            tetrs::mode::MAX_GARBAGE_ROWS
+        );
+    }
+
+    let input_map = tetrs::input::InputMap::load().unwrap_or_else(|err| {
+        log::warn!("Using the default key bindings: {:?}", err);
+        tetrs::input::InputMap::default()
+    });
+
+    let mut tetrs = tetrs::Tetrs::new(&window, &event_loop, rules, input_map)
         .await
         .context("Can't create tetrs")?;
 
+    if let Some(path) = option("--replay") {
+        tetrs.watch(tetrs::replay::Replay::open(std::path::Path::new(&path))?);
+    }
+
+    if let Some(url) = option("--connect") {
+        let room = option("--room").unwrap_or_else(|| tetrs::online::DEFAULT_ROOM.to_string());
+        tetrs.connect(&url, &room)?;
+    }
+
     Ok(tetrs::run(window, event_loop, tetrs).await?)
 }
+
+/// Value of a `--name value` command line option
+fn option(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
@@ -0,0 +1,164 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use super::scoring::{Clear, Spin};
+
+/// Extra rows sent for each clear in a row, by the number of clears before
+const COMBO_BONUS: [u32; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+/// Extra row for keeping a back-to-back chain going
+const BACK_TO_BACK_BONUS: u32 = 1;
+
+/// Rows of garbage a lock sends to the opponent, guideline style. `combo` is
+/// the number of clears in a row counting this one.
+pub fn rows(clear: &Clear, combo: u32, back_to_back: bool) -> u32 {
+    if clear.lines == 0 {
+        return 0;
+    }
+    let base = match (clear.spin, clear.lines) {
+        (Spin::None, 1) => 0,
+        (Spin::None, 2) => 1,
+        (Spin::None, 3) => 2,
+        (Spin::None, _) => 4,
+        (Spin::Mini, 1) => 0,
+        (Spin::Mini, _) => 1,
+        (Spin::Full, 1) => 2,
+        (Spin::Full, 2) => 4,
+        (Spin::Full, _) => 6,
+    };
+    let combo_idx = (combo.saturating_sub(1) as usize).min(COMBO_BONUS.len() - 1);
+    let bonus = if back_to_back { BACK_TO_BACK_BONUS } else { 0 };
+    base + COMBO_BONUS[combo_idx] + bonus
+}
+
+/// Garbage sent by the opponent that hasn't come in yet. Each attack waits out
+/// a delay, during which clearing lines cancels it instead of attacking back.
+#[derive(Clone, Debug, Default)]
+pub struct GarbageQueue {
+    /// Rows of each attack with the time they are due, oldest first
+    attacks: VecDeque<(u32, Duration)>,
+}
+
+impl GarbageQueue {
+    /// Queues an attack of `rows` rows to come in at `due`.
+    pub fn push(&mut self, rows: u32, due: Duration) {
+        if rows > 0 {
+            self.attacks.push_back((rows, due));
+        }
+    }
+
+    /// Takes `rows` off the queued garbage, oldest first. Returns the rows
+    /// left over to send to the opponent.
+    pub fn cancel(&mut self, mut rows: u32) -> u32 {
+        while rows > 0 {
+            match self.attacks.front_mut() {
+                Some((queued, _)) if *queued > rows => {
+                    *queued -= rows;
+                    rows = 0;
+                }
+                Some((queued, _)) => {
+                    rows -= *queued;
+                    self.attacks.pop_front();
+                }
+                None => break,
+            }
+        }
+        rows
+    }
+
+    /// Attacks due by `now`, at most `max` rows in total. What doesn't fit
+    /// stays in the queue.
+    pub fn take_due(&mut self, now: Duration, max: u32) -> Vec<u32> {
+        let mut taken = Vec::new();
+        let mut room = max;
+        while room > 0 {
+            match self.attacks.front_mut() {
+                Some((rows, due)) if *due <= now => {
+                    let rows_in = (*rows).min(room);
+                    *rows -= rows_in;
+                    room -= rows_in;
+                    taken.push(rows_in);
+                    if *rows == 0 {
+                        self.attacks.pop_front();
+                    }
+                }
+                _ => break,
+            }
+        }
+        taken
+    }
+
+    /// Rows of garbage waiting, due or not.
+    pub fn pending(&self) -> u32 {
+        self.attacks.iter().map(|(rows, _)| rows).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clear(lines: u8, spin: Spin) -> Clear {
+        Clear { lines, spin }
+    }
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn rows_by_clear() {
+        assert_eq!(rows(&clear(0, Spin::Full), 1, false), 0);
+        assert_eq!(rows(&clear(1, Spin::None), 1, false), 0);
+        assert_eq!(rows(&clear(2, Spin::None), 1, false), 1);
+        assert_eq!(rows(&clear(3, Spin::None), 1, false), 2);
+        assert_eq!(rows(&clear(4, Spin::None), 1, false), 4);
+        assert_eq!(rows(&clear(1, Spin::Mini), 1, false), 0);
+        assert_eq!(rows(&clear(2, Spin::Mini), 1, false), 1);
+        assert_eq!(rows(&clear(1, Spin::Full), 1, false), 2);
+        assert_eq!(rows(&clear(2, Spin::Full), 1, false), 4);
+        assert_eq!(rows(&clear(3, Spin::Full), 1, false), 6);
+    }
+
+    #[test]
+    fn rows_with_bonuses() {
+        let tetris = clear(4, Spin::None);
+        assert_eq!(rows(&tetris, 2, false), 4);
+        assert_eq!(rows(&tetris, 3, false), 5);
+        assert_eq!(rows(&tetris, 1, true), 5);
+        assert_eq!(rows(&tetris, 5, true), 4 + 2 + 1);
+        // Combo bonus stops growing at the end of the table
+        assert_eq!(rows(&tetris, 100, false), 4 + 5);
+    }
+
+    #[test]
+    fn cancel_across_attacks() {
+        let mut queue = GarbageQueue::default();
+        queue.push(2, secs(1));
+        queue.push(3, secs(2));
+        queue.push(4, secs(3));
+
+        assert_eq!(queue.cancel(6), 0);
+        assert_eq!(queue.pending(), 3);
+        assert_eq!(queue.take_due(secs(3), 8), [3]);
+
+        queue.push(2, secs(4));
+        assert_eq!(queue.cancel(5), 3);
+        assert_eq!(queue.pending(), 0);
+    }
+
+    #[test]
+    fn take_due_leaves_the_rest() {
+        let mut queue = GarbageQueue::default();
+        queue.push(5, secs(1));
+        queue.push(6, secs(1));
+        queue.push(3, secs(10));
+
+        assert_eq!(queue.take_due(secs(2), 8), [5, 3]);
+        assert_eq!(queue.pending(), 6);
+        assert_eq!(queue.take_due(secs(2), 8), [3]);
+        // Not due yet
+        assert!(queue.take_due(secs(2), 8).is_empty());
+        assert_eq!(queue.take_due(secs(10), 8), [3]);
+        assert_eq!(queue.pending(), 0);
+    }
+}
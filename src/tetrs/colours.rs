@@ -37,6 +37,7 @@ pub const RED: Colour = convert(0xD61355);
 pub const BROWN: Colour = convert(0xB99B6B);
 pub const LIGHT_BLUE: Colour = convert(0x93BFCF);
 pub const NAVY_BLUE: Colour = convert(0x362FD9);
+pub const SLATE: Colour = convert(0x4F5D6B);
 
 pub const UNRENDERED: Colour = Colour {
     red: 0,
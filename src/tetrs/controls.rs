@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Things the player can do in the game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateClockwise,
+    RotateCounterClockwise,
+    Rotate180,
+    Hold,
+}
+
+/// How held keys repeat.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Handling {
+    /// Delayed Auto Shift, how long a move is held before it starts repeating
+    pub das: Duration,
+    /// Auto Repeat Rate, time between repeated moves. Zero moves all the way at once.
+    pub arr: Duration,
+    /// How many times faster than gravity a soft drop is
+    pub soft_drop_factor: u32,
+}
+
+impl std::default::Default for Handling {
+    fn default() -> Self {
+        Handling {
+            das: Duration::from_millis(167),
+            arr: Duration::from_millis(33),
+            soft_drop_factor: 20,
+        }
+    }
+}
+
+/// Horizontal direction of a move, left is negative.
+pub type Direction = i8;
+
+/// Keeps track of the held move keys and charges the auto shift. Charge isn't
+/// tied to a tetromino, so it carries over to the next one.
+#[derive(Clone, Debug, Default)]
+pub struct AutoShift {
+    left: bool,
+    right: bool,
+    /// Direction of the latest pressed move key that's still held
+    direction: Direction,
+    /// How long the move key in `direction` has been held
+    charge: Duration,
+    /// Time since the last repeated move
+    repeat: Duration,
+}
+
+impl AutoShift {
+    pub fn press(&mut self, direction: Direction) {
+        if direction < 0 {
+            self.left = true;
+        } else {
+            self.right = true;
+        }
+        self.direction = direction;
+        self.charge = Duration::ZERO;
+        self.repeat = Duration::ZERO;
+    }
+
+    pub fn release(&mut self, direction: Direction) {
+        if direction < 0 {
+            self.left = false;
+        } else {
+            self.right = false;
+        }
+        if self.direction == direction {
+            // Fall back to the other key if it's still held
+            self.direction = match (self.left, self.right) {
+                (true, _) => -1,
+                (_, true) => 1,
+                _ => 0,
+            };
+            self.charge = Duration::ZERO;
+            self.repeat = Duration::ZERO;
+        }
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Number of repeated moves `elapsed` brings, `u32::MAX` meaning all the way.
+    pub fn step(&mut self, elapsed: Duration, handling: &Handling) -> u32 {
+        if self.direction == 0 {
+            return 0;
+        }
+
+        let before = self.charge;
+        self.charge += elapsed;
+        if self.charge < handling.das {
+            return 0;
+        }
+
+        if handling.arr.is_zero() {
+            return u32::MAX;
+        }
+
+        let mut moves = 0;
+        if before < handling.das {
+            // Just charged, first repeat is right away
+            moves += 1;
+            self.repeat = self.charge - handling.das;
+        } else {
+            self.repeat += elapsed;
+        }
+        let repeats = (self.repeat.as_nanos() / handling.arr.as_nanos()) as u32;
+        self.repeat -= handling.arr * repeats;
+
+        moves + repeats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handling(das: u64, arr: u64) -> Handling {
+        Handling {
+            das: Duration::from_millis(das),
+            arr: Duration::from_millis(arr),
+            ..Handling::default()
+        }
+    }
+
+    fn step(auto_shift: &mut AutoShift, millis: u64, handling: &Handling) -> u32 {
+        auto_shift.step(Duration::from_millis(millis), handling)
+    }
+
+    #[test]
+    fn repeats_after_das_every_arr() {
+        let handling = handling(100, 20);
+        let mut auto_shift = AutoShift::default();
+        auto_shift.press(1);
+        assert_eq!(step(&mut auto_shift, 99, &handling), 0);
+        // First repeat as soon as it's charged
+        assert_eq!(step(&mut auto_shift, 1, &handling), 1);
+        assert_eq!(step(&mut auto_shift, 19, &handling), 0);
+        assert_eq!(step(&mut auto_shift, 1, &handling), 1);
+        // What's left over counts towards the next repeat
+        assert_eq!(step(&mut auto_shift, 45, &handling), 2);
+        assert_eq!(step(&mut auto_shift, 15, &handling), 1);
+
+        auto_shift.release(1);
+        assert_eq!(step(&mut auto_shift, 1000, &handling), 0);
+    }
+
+    #[test]
+    fn charges_past_das_in_one_step() {
+        let handling = handling(100, 20);
+        let mut auto_shift = AutoShift::default();
+        auto_shift.press(-1);
+        assert_eq!(step(&mut auto_shift, 140, &handling), 3);
+        assert_eq!(auto_shift.direction(), -1);
+    }
+
+    #[test]
+    fn zero_arr_moves_all_the_way() {
+        let handling = handling(100, 0);
+        let mut auto_shift = AutoShift::default();
+        auto_shift.press(1);
+        assert_eq!(step(&mut auto_shift, 99, &handling), 0);
+        assert_eq!(step(&mut auto_shift, 1, &handling), u32::MAX);
+        assert_eq!(step(&mut auto_shift, 1, &handling), u32::MAX);
+    }
+}
@@ -1,211 +1,722 @@
-use anyhow::Context;
+use std::collections::VecDeque;
+use std::time::Duration;
 
+use super::attack::{self, GarbageQueue};
+use super::controls::{Action, AutoShift, Direction};
+use super::gravity;
+use super::mode::GameMode;
+use super::randomizer::{PieceGenerator, Rng};
+use super::rotation::{Orientation, Rotation, RotationSystem};
+use super::rules::{Rules, MAX_PREVIEW, MIN_PREVIEW};
+use super::scoring::{Clear, ScoringSystem, Spin};
 use super::tetromino::{BlockState, CurrentTetromino};
-use super::GameEvent;
 
+/// Visible rows of the board
 pub const NUM_ROWS: usize = 28;
 pub const NUM_COLS: usize = 12;
-const MAX_SPEED: u8 = 42;
-const MAX_LEVEL: u8 = 40;
-const SCORE_PER_LEVEL: u128 = 20;
+/// Hidden rows above the visible ones, tetrominos spawn here and the stack can
+/// grow into them
+pub const BUFFER_ROWS: usize = NUM_ROWS;
+pub const TOTAL_ROWS: usize = BUFFER_ROWS + NUM_ROWS;
+/// Most garbage rows that come in with a single lock, the rest waits for the
+/// next one
+const MAX_GARBAGE_PER_LOCK: u32 = 8;
+/// Mixed into the seed for the garbage holes
+const GARBAGE_SALT: u64 = 0x6A09_E667_F3BC_C908;
+/// Kick of the SRS tables that gives a full T-spin even if it looks like a mini
+const TST_KICK: usize = 4;
+/// Lines between the split times
+pub const SPLIT_LINES: u32 = 10;
+/// Fall this close to a whole row is a row, ticks are cut to whole nanoseconds
+/// so they add up to a hair less than the gravity asks for
+const FALL_TOLERANCE: f64 = 1e-6;
+
+/// What happened on the board as the result of a call into the [`GameState`].
+/// Engine doesn't know how these are presented, it's up to the caller to pass
+/// them on to wherever they are needed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    /// Gravity moved the current tetromino
+    Stepped,
+    /// Current tetromino is put on hold
+    Held,
+    /// Current tetromino is written into the board and the next one is in play
+    Locked,
+    /// Number of full lines removed from the board after a lock
+    LinesCleared(u8),
+    /// Rows of garbage to send to the opponent, left after cancelling the
+    /// garbage waiting to come in
+    Attack(u32),
+    /// Game is finished, board won't change anymore
+    GameOver,
+}
+
+/// How the holes of garbage rows coming in together are laid out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Holes {
+    /// All rows have their hole in the same column
+    Aligned,
+    /// Every row has its hole in a different column than the row below
+    Cheese,
+    /// Like cheese, with a second hole in about a third of the rows
+    Messy,
+}
+
+/// Why the game ended, following the guideline.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameOverReason {
+    /// A new tetromino came in overlapping the stack
+    BlockOut,
+    /// A tetromino locked completely above the visible rows
+    LockOut,
+    /// Stack was pushed above the buffer zone
+    TopOut,
+    /// Goal of the mode was reached
+    Completed,
+}
+
+impl GameOverReason {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameOverReason::BlockOut => "block out",
+            GameOverReason::LockOut => "lock out",
+            GameOverReason::TopOut => "top out",
+            GameOverReason::Completed => "complete",
+        }
+    }
+}
 
 pub struct GameState {
-    pub blocks: [[BlockState; NUM_COLS]; NUM_ROWS],
+    /// Buffer zone on top followed by the visible rows, row `y` of the board is
+    /// at `y + BUFFER_ROWS`
+    pub blocks: [[BlockState; NUM_COLS]; TOTAL_ROWS],
     pub score: u128,
     pub level: u8,
+    /// Number of lines cleared so far
+    pub lines: u32,
+    /// Number of line clearing locks in a row
+    pub combo: u32,
+    /// Last line clear was a difficult one, next difficult one gets the bonus
+    pub back_to_back: bool,
+    /// Last lock that cleared lines or was a spin
+    pub last_clear: Option<Clear>,
     pub current_tetromino: CurrentTetromino,
-    pub next_tetromino: CurrentTetromino,
-    pub time_elapsed: u8,
+    /// Upcoming tetrominos, first one is the next in play
+    pub next_tetrominos: VecDeque<CurrentTetromino>,
+    /// Tetromino put aside to be swapped back in later
+    pub held_tetromino: Option<BlockState>,
+    /// Hold is allowed once until the next lock
+    pub can_hold: bool,
+    /// Time played so far
+    pub time_elapsed: Duration,
     pub steps_elapsed: u128,
-    finished: bool,
+    /// Time played when the first key was pressed, the clock of timed modes
+    /// starts from there
+    pub started: Option<Duration>,
+    /// Number of tetrominos locked
+    pub pieces: u32,
+    /// Number of keys pressed
+    pub key_presses: u32,
+    /// Play time every `SPLIT_LINES` lines were cleared at
+    pub splits: Vec<Duration>,
+    /// Number of garbage rows pushed up into the board
+    pub garbage_added: u32,
+    /// Garbage sent by the opponent, waiting to come in
+    pub garbage_queue: GarbageQueue,
+    /// Rows of garbage sent to the opponent
+    pub garbage_sent: u32,
+    /// Rows gravity owes the current tetromino, fractions are carried over to
+    /// the next step
+    fall: f64,
+    /// Held move keys
+    auto_shift: AutoShift,
+    /// Soft drop key is held
+    soft_dropping: bool,
+    /// How long the current tetromino has been resting on the stack
+    pub lock_timer: Duration,
+    /// Number of times moving or rotating restarted the lock delay
+    pub lock_resets: u8,
+    /// Lowest row the current tetromino reached, reaching a lower one gives back
+    /// all the lock resets
+    lowest_y: i8,
+    /// Seed of the piece generator, same seed gives the same game
+    pub seed: u64,
+    pub rules: Rules,
+    generator: Box<dyn PieceGenerator>,
+    /// Source of the garbage holes, seeded from the game's seed too
+    garbage_rng: Rng,
+    rotation_system: Box<dyn RotationSystem>,
+    scoring: Box<dyn ScoringSystem>,
+    mode: Box<dyn GameMode>,
+    /// Rotation and the kick used, if the last thing the current tetromino did
+    /// was a rotation
+    last_rotation: Option<(Rotation, usize)>,
+    /// Set once the game is finished
+    pub game_over: Option<GameOverReason>,
 }
 
 impl std::default::Default for GameState {
     fn default() -> Self {
-        GameState {
-            blocks: [[BlockState::Emp; NUM_COLS]; NUM_ROWS],
+        GameState::new(random_number::random!(), Rules::default())
+    }
+}
+
+impl GameState {
+    pub fn new(seed: u64, rules: Rules) -> Self {
+        let mode = rules.mode.game_mode(&rules);
+        let mut generator = rules.randomizer.generator(seed);
+        let current_tetromino: CurrentTetromino = generator.next().into();
+        let scoring = rules.scoring.system();
+        let next_tetrominos = (0..rules.preview_count.clamp(MIN_PREVIEW, MAX_PREVIEW))
+            .map(|_| generator.next().into())
+            .collect();
+
+        let mut game_state = GameState {
+            blocks: [[BlockState::Emp; NUM_COLS]; TOTAL_ROWS],
             score: 0,
-            level: 0,
-            time_elapsed: 0,
+            level: scoring.level(0),
+            lines: 0,
+            combo: 0,
+            back_to_back: false,
+            last_clear: None,
+            time_elapsed: Duration::ZERO,
             steps_elapsed: 0,
-            current_tetromino: CurrentTetromino::next_one(),
-            next_tetromino: CurrentTetromino::next_one(),
-            finished: false,
+            started: None,
+            pieces: 0,
+            key_presses: 0,
+            splits: Vec::new(),
+            garbage_added: 0,
+            garbage_queue: GarbageQueue::default(),
+            garbage_sent: 0,
+            fall: 0.0,
+            auto_shift: AutoShift::default(),
+            soft_dropping: false,
+            lock_timer: Duration::ZERO,
+            lock_resets: 0,
+            lowest_y: current_tetromino.y,
+            current_tetromino,
+            next_tetrominos,
+            held_tetromino: None,
+            can_hold: true,
+            seed,
+            generator,
+            // Pieces already use the seed as it is, garbage gets its own stream
+            garbage_rng: Rng::new(seed.rotate_left(32) ^ GARBAGE_SALT),
+            rotation_system: rules.rotation.system(),
+            scoring,
+            mode,
+            last_rotation: None,
+            rules,
+            game_over: None,
+        };
+        if let Some((rows, holes)) = game_state.mode.garbage(&game_state) {
+            game_state.add_garbage(rows, holes);
         }
+        game_state
     }
-}
 
-impl GameState {
-    pub fn step_time(
-        &mut self,
-        event_loop: &winit::event_loop::EventLoopProxy<GameEvent>,
-    ) -> anyhow::Result<()> {
-        if !self.finished {
-            if self.time_elapsed > self.current_speed() {
-                self.update_blocks();
-                self.time_elapsed = 0;
-                event_loop
-                    .send_event(GameEvent::Step)
-                    .context("Couldn't send GameEvent::Step")?;
-            }
-            self.time_elapsed += 1;
+    /// Rows of the board that are shown, without the buffer zone.
+    pub fn visible_blocks(&self) -> &[[BlockState; NUM_COLS]] {
+        &self.blocks[BUFFER_ROWS..]
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.game_over.is_some()
+    }
+
+    pub fn mode(&self) -> &dyn GameMode {
+        self.mode.as_ref()
+    }
+
+    /// Number of rows with garbage still in them.
+    pub fn garbage_left(&self) -> u32 {
+        self.blocks
+            .iter()
+            .filter(|row| row.contains(&BlockState::Gar))
+            .count() as u32
+    }
+
+    /// Pushes the stack up by `rows` rows of garbage, each with a hole laid out
+    /// the way `holes` says. The current tetromino is pushed up with the stack
+    /// if it's in the way. Game is over if anything is pushed out of the top.
+    pub fn add_garbage(&mut self, rows: usize, holes: Holes) -> Vec<Outcome> {
+        if self.is_finished() || rows == 0 {
+            return Vec::new();
+        }
+        let rows = rows.min(TOTAL_ROWS);
+        let pushed_out = self.blocks[..rows]
+            .iter()
+            .any(|row| row.iter().any(|col| *col != BlockState::Emp));
+
+        self.blocks.copy_within(rows.., 0);
+        let mut hole = self.garbage_rng.below(NUM_COLS);
+        for row in (TOTAL_ROWS - rows..TOTAL_ROWS).rev() {
+            if row != TOTAL_ROWS - 1 && holes != Holes::Aligned {
+                hole = (hole + 1 + self.garbage_rng.below(NUM_COLS - 1)) % NUM_COLS;
+            }
+            self.blocks[row] = [BlockState::Gar; NUM_COLS];
+            self.blocks[row][hole] = BlockState::Emp;
+            if holes == Holes::Messy && self.garbage_rng.below(3) == 0 {
+                self.blocks[row][self.garbage_rng.below(NUM_COLS)] = BlockState::Emp;
+            }
+        }
+        self.garbage_added += rows as u32;
+
+        // Current tetromino rides up on the garbage
+        let mut lifted = 0;
+        while !self.can_do(&self.current_tetromino) && lifted < rows {
+            self.current_tetromino.y -= 1;
+            lifted += 1;
+        }
+        self.lowest_y -= lifted as i8;
+
+        if pushed_out || self.topped_out() || !self.can_do(&self.current_tetromino) {
+            self.finish_game(GameOverReason::TopOut);
+            return vec![Outcome::GameOver];
+        }
+        vec![Outcome::Stepped]
+    }
+
+    /// Takes an attack of `rows` rows from the opponent. It comes in after the
+    /// garbage delay, unless it's cancelled by clearing lines before that.
+    pub fn receive(&mut self, rows: u32) {
+        if !self.is_finished() {
+            let due = self.time_elapsed + self.rules.garbage_delay;
+            self.garbage_queue.push(rows, due);
+        }
+    }
+
+    /// Time since the first key was pressed.
+    pub fn play_time(&self) -> Duration {
+        self.started
+            .map_or(Duration::ZERO, |started| self.time_elapsed - started)
+    }
+
+    /// Moves the game forward, `elapsed` is the time passed since the last step.
+    pub fn step_time(&mut self, elapsed: Duration) -> Vec<Outcome> {
+        let mut outcomes = Vec::new();
+        if !self.is_finished() {
+            self.time_elapsed += elapsed;
             self.steps_elapsed += 1;
-        } else {
-            event_loop
-                .send_event(GameEvent::Finished)
-                .context("Couldn't send GameEvent::Finished")?;
+            if self.mode.completed(self) {
+                self.finish_game(GameOverReason::Completed);
+                return vec![Outcome::GameOver];
+            }
+
+            let mut stepped = false;
+            let direction = self.auto_shift.direction();
+            let shifts = self.auto_shift.step(elapsed, &self.rules.handling);
+            for _ in 0..shifts {
+                if !self.shift(direction) {
+                    break;
+                }
+                stepped = true;
+            }
+
+            let gravity = if self.started.is_none() && self.mode.clock() {
+                // Clock starts with the first key, the tetromino waits for it
+                0.0
+            } else if self.soft_dropping {
+                let factor = self.rules.handling.soft_drop_factor as f64;
+                (self.gravity() * factor).min(gravity::MAX_GRAVITY)
+            } else {
+                self.gravity()
+            };
+            self.fall += gravity * elapsed.as_secs_f64();
+            while self.fall >= 1.0 - FALL_TOLERANCE {
+                self.fall -= 1.0;
+                if !self.move_down() {
+                    // Resting on the stack, nothing to carry over
+                    self.fall = 0.0;
+                    break;
+                }
+                if self.soft_dropping {
+                    self.score += self.scoring.soft_drop(1);
+                }
+                stepped = true;
+            }
+            if stepped {
+                outcomes.push(Outcome::Stepped);
+            }
+
+            outcomes.extend(self.step_lock_delay(elapsed));
         }
-        Ok(())
+        outcomes
     }
 
-    pub fn tetromino_down(&mut self) {
-        if self.can_move(0, 1) {
-            self.current_tetromino.down();
-        } else {
-            self.commit();
-            self.current_tetromino = self.next_tetromino.clone();
-            self.next_tetromino = CurrentTetromino::next_one();
+    /// Player pressing the key of an `action`. Moves and soft drop keep
+    /// repeating until they are released.
+    pub fn press(&mut self, action: Action) -> Vec<Outcome> {
+        if self.is_finished() {
+            return Vec::new();
+        }
+        self.started.get_or_insert(self.time_elapsed);
+        self.key_presses += 1;
+
+        match action {
+            Action::MoveLeft => {
+                self.auto_shift.press(-1);
+                self.tetromino_left();
+                Vec::new()
+            }
+            Action::MoveRight => {
+                self.auto_shift.press(1);
+                self.tetromino_right();
+                Vec::new()
+            }
+            Action::SoftDrop => {
+                self.soft_dropping = true;
+                self.tetromino_soft_drop()
+            }
+            Action::HardDrop => self.tetromino_hard_drop(),
+            Action::RotateClockwise => {
+                self.tetromino_rotate(Rotation::Clockwise);
+                Vec::new()
+            }
+            Action::RotateCounterClockwise => {
+                self.tetromino_rotate(Rotation::CounterClockwise);
+                Vec::new()
+            }
+            Action::Rotate180 => {
+                self.tetromino_rotate(Rotation::Half);
+                Vec::new()
+            }
+            Action::Hold => self.tetromino_hold(),
         }
     }
 
-    pub fn tetromino_right(&mut self) {
-        if self.can_move(1, 0) {
-            self.current_tetromino.right();
+    /// Player letting go of the key of an `action`.
+    pub fn release(&mut self, action: Action) {
+        match action {
+            Action::MoveLeft => self.auto_shift.release(-1),
+            Action::MoveRight => self.auto_shift.release(1),
+            Action::SoftDrop => self.soft_dropping = false,
+            _ => {}
+        }
+    }
+
+    /// Player moving the current tetromino one row down, scores a soft drop.
+    /// Locking is left to the lock delay.
+    pub fn tetromino_soft_drop(&mut self) -> Vec<Outcome> {
+        if !self.is_finished() && self.move_down() {
+            self.score += self.scoring.soft_drop(1);
         }
+        Vec::new()
+    }
+
+    pub fn tetromino_right(&mut self) {
+        self.shift(1);
     }
 
     pub fn tetromino_left(&mut self) {
-        if self.can_move(-1, 0) {
-            self.current_tetromino.left();
+        self.shift(-1);
+    }
+
+    /// Rotates the current tetromino, trying the kicks of the rotation system
+    /// in order until one fits.
+    pub fn tetromino_rotate(&mut self, rotation: Rotation) {
+        let tetromino = &self.current_tetromino.tetromino;
+        let kicks = self
+            .rotation_system
+            .kicks(tetromino.kind, tetromino.orientation, rotation);
+
+        for (kick, (dx, dy)) in kicks.into_iter().enumerate() {
+            let mut tetro = self.current_tetromino.clone();
+            tetro.rotate(rotation);
+            tetro.x += dx;
+            tetro.y += dy;
+            if self.can_do(&tetro) {
+                self.current_tetromino = tetro;
+                self.last_rotation = Some((rotation, kick));
+                self.reset_lock_delay();
+                return;
+            }
         }
     }
 
-    pub fn tetromino_rotate(&mut self) {
-        if self.can_rotate() {
-            self.current_tetromino.rotate();
+    /// Drops the current tetromino all the way down and locks it.
+    pub fn tetromino_hard_drop(&mut self) -> Vec<Outcome> {
+        if self.is_finished() {
+            return Vec::new();
+        }
+
+        let ghost = self.ghost_tetromino();
+        let cells = (ghost.y - self.current_tetromino.y) as u32;
+        if cells > 0 {
+            self.score += self.scoring.hard_drop(cells);
+            self.last_rotation = None;
         }
+        self.current_tetromino = ghost;
+        self.lock()
     }
 
-    pub fn current_speed(&self) -> u8 {
-        if self.level > MAX_LEVEL {
-            MAX_SPEED - MAX_LEVEL
-        } else {
-            MAX_SPEED - self.level
+    /// Where the current tetromino would land if it was dropped straight down.
+    pub fn ghost_tetromino(&self) -> CurrentTetromino {
+        let mut ghost = self.current_tetromino.clone();
+        let mut dy = 0;
+        while self.can_move(0, dy + 1) {
+            dy += 1;
         }
+        ghost.y += dy;
+        ghost
     }
 
-    fn commit(&mut self) {
-        let (t_width, t_height) = (
-            self.current_tetromino.tetromino.shape[0].len(),
-            self.current_tetromino.tetromino.shape.len(),
-        );
+    /// Swaps the current tetromino with the held one, or with the next one if
+    /// there is nothing on hold.
+    pub fn tetromino_hold(&mut self) -> Vec<Outcome> {
+        if self.is_finished() || !self.can_hold {
+            return Vec::new();
+        }
+
+        let current = self.current_tetromino.tetromino.kind;
+        let next = match self.held_tetromino.replace(current) {
+            Some(held) => held.into(),
+            None => self.next_from_queue(),
+        };
+        self.can_hold = false;
 
-        if self.current_tetromino.y < 0 {
-            // Tetromino dropped while off screen, game over.
-            self.finish_game();
-            return;
+        let mut outcomes = vec![Outcome::Held];
+        outcomes.extend(self.bring_in(next));
+        outcomes
+    }
+
+    /// Falling speed at the current level, in cells per second.
+    pub fn gravity(&self) -> f64 {
+        gravity::guideline(self.level)
+    }
+
+    /// Writes the current tetromino into the board, clears the lines and brings
+    /// in the next tetromino.
+    fn lock(&mut self) -> Vec<Outcome> {
+        // Spin is decided by the board before the tetromino is in it
+        let spin = self.spin();
+        self.commit();
+        if self.current_tetromino.cells().all(|(_, y)| y < 0) {
+            self.finish_game(GameOverReason::LockOut);
+            return vec![Outcome::GameOver];
         }
 
-        let (start_x, start_y) = (self.current_tetromino.x, self.current_tetromino.y as usize);
+        self.pieces += 1;
 
-        for (y, row) in (start_y..start_y + t_height).enumerate() {
-            for (x, col) in (start_x..start_x + t_width).enumerate() {
-                let tetro_bit = self.current_tetromino.tetromino.shape[y][x];
-                if tetro_bit != BlockState::Emp {
-                    self.blocks[row][col] = self.current_tetromino.tetromino.shape[y][x];
-                }
+        let mut outcomes = vec![Outcome::Locked];
+        let n = self.remove_lines();
+        if n > 0 {
+            outcomes.push(Outcome::LinesCleared(n));
+        }
+        let clear = Clear { lines: n, spin };
+        let back_to_back = self.update_score(clear);
+        let attack = self
+            .garbage_queue
+            .cancel(attack::rows(&clear, self.combo, back_to_back));
+        if attack > 0 {
+            self.garbage_sent += attack;
+            outcomes.push(Outcome::Attack(attack));
+        }
+        if self.mode.completed(self) {
+            self.finish_game(GameOverReason::Completed);
+            outcomes.push(Outcome::GameOver);
+            return outcomes;
+        }
+        if self.topped_out() {
+            self.finish_game(GameOverReason::TopOut);
+            outcomes.push(Outcome::GameOver);
+            return outcomes;
+        }
+
+        let next = self.next_from_queue();
+        self.can_hold = true;
+        outcomes.extend(self.bring_in(next));
+        // Garbage comes in under the next tetromino, the mode decides how much
+        if let Some((rows, holes)) = self.mode.garbage(self) {
+            outcomes.extend(self.add_garbage(rows, holes));
+        }
+        // Opponent's garbage only comes in with locks that don't clear lines
+        if n == 0 {
+            let due = self
+                .garbage_queue
+                .take_due(self.time_elapsed, MAX_GARBAGE_PER_LOCK);
+            for rows in due {
+                outcomes.extend(self.add_garbage(rows as usize, Holes::Aligned));
             }
         }
+        outcomes
     }
 
-    fn finish_game(&mut self) {
-        self.finished = true;
+    /// Puts a new tetromino in play with a fresh lock delay, the game is over
+    /// if it doesn't fit.
+    fn bring_in(&mut self, tetromino: CurrentTetromino) -> Vec<Outcome> {
+        self.last_rotation = None;
+        self.fall = 0.0;
+        self.lowest_y = tetromino.y;
+        self.current_tetromino = tetromino;
+        self.lock_timer = Duration::ZERO;
+        self.lock_resets = 0;
+
+        if self.can_do(&self.current_tetromino) {
+            Vec::new()
+        } else {
+            self.finish_game(GameOverReason::BlockOut);
+            vec![Outcome::GameOver]
+        }
     }
 
-    fn can_move(&self, dx: i8, dy: i8) -> bool {
-        let mut tetro = self.current_tetromino.clone();
-        let x = tetro.x as i8 + dx;
-        tetro.x = if x >= 0 { x as usize } else { 0 };
-        tetro.y += dy;
-        self.can_do(&tetro)
+    /// Stack reached the top row of the buffer zone, there's no room above it.
+    fn topped_out(&self) -> bool {
+        self.blocks[0].iter().any(|col| *col != BlockState::Emp)
     }
 
-    fn can_rotate(&self) -> bool {
-        let mut tetro = self.current_tetromino.clone();
-        tetro.tetromino.rotate();
-        self.can_do(&tetro)
+    /// Counts down the lock delay while the current tetromino rests on the stack
+    /// and locks it when the time is up. Lifting it off the stack only pauses
+    /// the count, otherwise it could be kept in the air forever.
+    fn step_lock_delay(&mut self, elapsed: Duration) -> Vec<Outcome> {
+        if self.can_move(0, 1) {
+            return Vec::new();
+        }
+
+        self.lock_timer += elapsed;
+        if self.lock_timer >= self.rules.lock_delay {
+            self.lock()
+        } else {
+            Vec::new()
+        }
     }
 
-    fn can_do(&self, ctetro: &CurrentTetromino) -> bool {
-        let (tetro_width, tetro_height) = {
-            (
-                ctetro.tetromino.shape[0].len() as i8,
-                ctetro.tetromino.shape.len() as i8,
-            )
-        };
+    /// Moving or rotating on the stack restarts the lock delay, but only so many
+    /// times.
+    fn reset_lock_delay(&mut self) {
+        let landing = self.lock_timer > Duration::ZERO || !self.can_move(0, 1);
+        if landing && self.lock_resets < self.rules.max_lock_resets {
+            self.lock_timer = Duration::ZERO;
+            self.lock_resets += 1;
+        }
+    }
 
-        // with the movement
-        let (tetro_blocks_x, tetro_blocks_y) = (ctetro.x as i8, ctetro.y);
+    fn shift(&mut self, direction: Direction) -> bool {
+        if !self.can_move(direction, 0) {
+            return false;
+        }
 
-        let (board_width, board_height) = (NUM_COLS as i8, NUM_ROWS as i8);
-        let (board_x, board_y) = (0, 0);
+        if direction < 0 {
+            self.current_tetromino.left();
+        } else {
+            self.current_tetromino.right();
+        }
+        self.last_rotation = None;
+        self.reset_lock_delay();
+        true
+    }
 
-        // Check board bounds
-        if tetro_blocks_x < board_x
-            || tetro_blocks_x + tetro_width > board_x + board_width
-            || tetro_blocks_y + tetro_height < board_y
-            || tetro_blocks_y + tetro_height > board_y + board_height
-        {
+    fn move_down(&mut self) -> bool {
+        if !self.can_move(0, 1) {
             return false;
         }
 
-        // Check if it intersects with the board
-        // Visible piece of the tetromino
-        let in_view = tetro_blocks_y + tetro_height;
+        self.current_tetromino.down();
+        self.last_rotation = None;
+        if self.current_tetromino.y > self.lowest_y {
+            // Step reset
+            self.lowest_y = self.current_tetromino.y;
+            self.lock_resets = 0;
+            self.lock_timer = Duration::ZERO;
+        }
+        true
+    }
 
-        // Intersected part of the board
-        let (board_row_start, board_row_end) = {
-            if in_view < tetro_height {
-                (0, in_view as usize)
-            } else {
-                let bend = (tetro_blocks_y + tetro_height) as usize;
-                if bend < NUM_ROWS {
-                    (
-                        (tetro_blocks_y) as usize,
-                        (tetro_blocks_y + tetro_height) as usize, // Rebate the off screen starting
-                    )
-                } else {
-                    ((tetro_blocks_y) as usize, NUM_ROWS)
-                }
-            }
+    /// T-spin check with the 3-corner rule, at least three of the four corners
+    /// around the center of the T are taken. It's a full T-spin if both corners
+    /// on the side the T points to are, or the T got in with the kick of a
+    /// T-spin triple, otherwise it's a mini.
+    fn spin(&self) -> Spin {
+        let tetro = &self.current_tetromino;
+        let (rotation, kick) = match self.last_rotation {
+            Some(last_rotation) if tetro.tetromino.kind == BlockState::Tee => last_rotation,
+            _ => return Spin::None,
+        };
+
+        let (cx, cy) = (tetro.x + 1, tetro.y + 1);
+        let (front, back) = match tetro.tetromino.orientation {
+            Orientation::Spawn => (
+                [(cx - 1, cy - 1), (cx + 1, cy - 1)],
+                [(cx - 1, cy + 1), (cx + 1, cy + 1)],
+            ),
+            Orientation::Right => (
+                [(cx + 1, cy - 1), (cx + 1, cy + 1)],
+                [(cx - 1, cy - 1), (cx - 1, cy + 1)],
+            ),
+            Orientation::Reverse => (
+                [(cx - 1, cy + 1), (cx + 1, cy + 1)],
+                [(cx - 1, cy - 1), (cx + 1, cy - 1)],
+            ),
+            Orientation::Left => (
+                [(cx - 1, cy - 1), (cx - 1, cy + 1)],
+                [(cx + 1, cy - 1), (cx + 1, cy + 1)],
+            ),
         };
 
-        let (board_col_start, board_col_end) = (
-            tetro_blocks_x as usize,
-            (tetro_blocks_x + tetro_width) as usize,
+        // Walls and the floor count as taken
+        let taken = |(x, y): &(i8, i8)| self.occupied(*x, *y);
+        let (front, back) = (
+            front.iter().filter(|c| taken(c)).count(),
+            back.iter().filter(|c| taken(c)).count(),
         );
 
-        for (drow, row) in self.blocks[board_row_start..board_row_end]
-            .iter()
-            .enumerate()
-        {
-            for (dcol, block) in row[board_col_start..board_col_end].iter().enumerate() {
-                //
-                let tetro_block = &ctetro.tetromino.shape[drow][dcol];
-                if *tetro_block != BlockState::Emp && *block != BlockState::Emp {
-                    return false;
-                }
-            }
+        if front + back < 3 {
+            Spin::None
+        } else if front == 2 || (rotation != Rotation::Half && kick == TST_KICK) {
+            Spin::Full
+        } else {
+            Spin::Mini
         }
-        true
+    }
+
+    /// Takes the first of the upcoming tetrominos and tops up the queue.
+    fn next_from_queue(&mut self) -> CurrentTetromino {
+        self.next_tetrominos.push_back(self.generator.next().into());
+        self.next_tetrominos
+            .pop_front()
+            .expect("Queue of the upcoming tetrominos is never empty")
+    }
+
+    /// Writes the current tetromino into the board, the parts in the buffer
+    /// zone too.
+    fn commit(&mut self) {
+        let kind = self.current_tetromino.tetromino.kind;
+        for (x, y) in self.current_tetromino.cells() {
+            self.blocks[(y + BUFFER_ROWS as i8) as usize][x as usize] = kind;
+        }
+    }
+
+    fn finish_game(&mut self, reason: GameOverReason) {
+        self.game_over = Some(reason);
+    }
+
+    fn can_move(&self, dx: i8, dy: i8) -> bool {
+        let mut tetro = self.current_tetromino.clone();
+        tetro.x += dx;
+        tetro.y += dy;
+        self.can_do(&tetro)
+    }
+
+    fn can_do(&self, ctetro: &CurrentTetromino) -> bool {
+        ctetro.cells().all(|(x, y)| !self.occupied(x, y))
+    }
+
+    /// Cell at `x`, `y` is taken, everything outside the board including the
+    /// top of the buffer zone counts as taken.
+    fn occupied(&self, x: i8, y: i8) -> bool {
+        let row = y + BUFFER_ROWS as i8;
+        if x < 0 || x >= NUM_COLS as i8 || row < 0 || row >= TOTAL_ROWS as i8 {
+            return true;
+        }
+        self.blocks[row as usize][x as usize] != BlockState::Emp
     }
 
     fn remove_lines(&mut self) -> u8 {
-        let mut new_blocks = [[BlockState::Emp; NUM_COLS]; NUM_ROWS];
+        let mut new_blocks = [[BlockState::Emp; NUM_COLS]; TOTAL_ROWS];
 
         let mut num_removed = 0;
 
-        let mut copy_to = NUM_ROWS;
+        let mut copy_to = TOTAL_ROWS;
         for row in self.blocks.iter().rev() {
             let unfilled = row
                 .iter()
@@ -225,21 +736,518 @@ impl GameState {
         num_removed
     }
 
-    fn update_score(&mut self, num_removed: u8) {
-        if num_removed > 0 {
-            self.score += num_removed as u128;
+    /// Scores a lock, returns whether it got the back-to-back bonus.
+    fn update_score(&mut self, clear: Clear) -> bool {
+        let back_to_back = if clear.lines > 0 {
+            self.combo += 1;
+            let back_to_back = self.back_to_back && clear.is_difficult();
+            self.back_to_back = clear.is_difficult();
+            back_to_back
+        } else {
+            self.combo = 0;
+            false
+        };
+
+        self.score += self
+            .scoring
+            .clear(&clear, self.level, self.combo, back_to_back);
+
+        if clear.lines > 0 || clear.spin != Spin::None {
+            self.last_clear = Some(clear);
+        }
+        self.lines += clear.lines as u32;
+        while self.lines >= (self.splits.len() as u32 + 1) * SPLIT_LINES {
+            self.splits.push(self.play_time());
         }
 
         self.update_level();
+        back_to_back
     }
 
     fn update_level(&mut self) {
-        self.level = (self.score / SCORE_PER_LEVEL) as u8;
+        self.level = self.scoring.level(self.lines).min(self.mode.max_level());
     }
+}
 
-    fn update_blocks(&mut self) {
-        self.tetromino_down();
-        let n = self.remove_lines();
-        self.update_score(n);
+#[cfg(test)]
+mod tests {
+    use super::super::mode::{Mode, MARATHON_LEVELS, MARATHON_LINES, SPRINT_LINES};
+    use super::super::scoring::ScoringStyle;
+    use super::*;
+
+    /// Fills the bottom row but the cells the current tetromino lands on.
+    fn fill_bottom_row(game_state: &mut GameState) {
+        let bottom = TOTAL_ROWS - 1;
+        game_state.blocks[bottom] = [BlockState::Gar; NUM_COLS];
+        for (x, y) in game_state.ghost_tetromino().cells() {
+            if (y + BUFFER_ROWS as i8) as usize == bottom {
+                game_state.blocks[bottom][x as usize] = BlockState::Emp;
+            }
+        }
+    }
+
+    #[test]
+    fn hard_drop_locks() {
+        let mut game_state = GameState::new(1, Rules::default());
+        let ghost = game_state.ghost_tetromino();
+        let kind = game_state.current_tetromino.tetromino.kind;
+        let cells = (ghost.y - game_state.current_tetromino.y) as u128;
+        assert_eq!(ghost.x, game_state.current_tetromino.x);
+
+        let outcomes = game_state.press(Action::HardDrop);
+        assert_eq!(outcomes.first(), Some(&Outcome::Locked));
+        assert!(!outcomes.contains(&Outcome::LinesCleared(1)));
+        assert_eq!(game_state.pieces, 1);
+        // Lands where the ghost was, 2 points for every cell dropped
+        for (x, y) in ghost.cells() {
+            assert!(game_state.blocks[(y + BUFFER_ROWS as i8) as usize][x as usize] == kind);
+        }
+        assert!(ghost.cells().any(|(_, y)| y == NUM_ROWS as i8 - 1));
+        assert_eq!(game_state.score, 2 * cells);
+    }
+
+    #[test]
+    fn full_row_is_cleared() {
+        let mut game_state = GameState::new(1, Rules::default());
+        fill_bottom_row(&mut game_state);
+        let outcomes = game_state.press(Action::HardDrop);
+        assert_eq!(outcomes[..2], [Outcome::Locked, Outcome::LinesCleared(1)]);
+        assert_eq!(game_state.lines, 1);
+    }
+
+    #[test]
+    fn one_hold_until_the_next_lock() {
+        let mut game_state = GameState::new(1, Rules::default());
+        let first = game_state.current_tetromino.tetromino.kind;
+        let next = game_state.next_tetrominos[0].tetromino.kind;
+
+        assert_eq!(game_state.press(Action::Hold), [Outcome::Held]);
+        assert!(game_state.current_tetromino.tetromino.kind == next);
+        assert!(game_state.held_tetromino == Some(first));
+
+        // Holding again before a lock doesn't swap it back
+        assert!(game_state.press(Action::Hold).is_empty());
+        assert!(game_state.current_tetromino.tetromino.kind == next);
+        assert!(game_state.held_tetromino == Some(first));
+
+        game_state.press(Action::HardDrop);
+        let after_lock = game_state.current_tetromino.tetromino.kind;
+        assert_eq!(game_state.press(Action::Hold), [Outcome::Held]);
+        assert!(game_state.current_tetromino.tetromino.kind == first);
+        assert!(game_state.held_tetromino == Some(after_lock));
+    }
+
+    /// Steps the game `millis` milliseconds at a time, `times` times.
+    fn wait(game_state: &mut GameState, millis: u64, times: u32) {
+        for _ in 0..times {
+            game_state.step_time(Duration::from_millis(millis));
+        }
+    }
+
+    #[test]
+    fn locks_after_the_lock_delay() {
+        let mut game_state = GameState::new(1, Rules::default());
+        game_state.current_tetromino = game_state.ghost_tetromino();
+
+        wait(&mut game_state, 10, 49);
+        assert_eq!(game_state.pieces, 0);
+        wait(&mut game_state, 10, 1);
+        assert_eq!(game_state.pieces, 1);
+    }
+
+    #[test]
+    fn lock_resets_run_out() {
+        let mut game_state = GameState::new(1, Rules::default());
+        game_state.current_tetromino = game_state.ghost_tetromino();
+
+        for reset in 0..game_state.rules.max_lock_resets {
+            wait(&mut game_state, 400, 1);
+            if reset % 2 == 0 {
+                game_state.tetromino_left();
+            } else {
+                game_state.tetromino_right();
+            }
+            assert_eq!(game_state.lock_timer, Duration::ZERO);
+        }
+        assert_eq!(game_state.pieces, 0);
+        assert_eq!(game_state.lock_resets, 15);
+
+        // No more resets, the delay runs out from the last one
+        wait(&mut game_state, 400, 1);
+        game_state.tetromino_left();
+        assert_eq!(game_state.lock_timer, Duration::from_millis(400));
+        wait(&mut game_state, 100, 1);
+        assert_eq!(game_state.pieces, 1);
+    }
+
+    #[test]
+    fn step_reset_gives_back_the_resets() {
+        let mut game_state = GameState::new(1, Rules::default());
+        // Ledge a row high at the left of the board
+        game_state.blocks[TOTAL_ROWS - 1][..7].fill(BlockState::Gar);
+        let y = NUM_ROWS as i8 - 3;
+        place(&mut game_state, BlockState::Ohh, None, 1, y);
+        game_state.lowest_y = y;
+
+        wait(&mut game_state, 100, 1);
+        for reset in 0..game_state.rules.max_lock_resets {
+            if reset % 2 == 0 {
+                game_state.tetromino_left();
+            } else {
+                game_state.tetromino_right();
+            }
+        }
+        assert_eq!(game_state.lock_resets, 15);
+
+        // Off the ledge and down a row
+        while game_state.current_tetromino.x < 7 {
+            game_state.tetromino_right();
+        }
+        while game_state.current_tetromino.y == y {
+            wait(&mut game_state, 10, 1);
+        }
+        assert_eq!(game_state.pieces, 0);
+        assert_eq!(game_state.lock_resets, 0);
+        game_state.tetromino_right();
+        assert_eq!(game_state.lock_resets, 1);
+    }
+
+    #[test]
+    fn fall_carries_over_ticks() {
+        let mut game_state = GameState::new(1, Rules::default());
+        let spawn_y = game_state.current_tetromino.y;
+        for _ in 0..59 {
+            game_state.step_time(super::super::TICK);
+        }
+        assert_eq!(game_state.current_tetromino.y, spawn_y);
+        game_state.step_time(super::super::TICK);
+        assert_eq!(game_state.current_tetromino.y, spawn_y + 1);
+    }
+
+    #[test]
+    fn soft_drop_is_capped() {
+        let mut game_state = GameState::new(1, Rules::default());
+        game_state.level = 20;
+        game_state.press(Action::SoftDrop);
+        let y = game_state.current_tetromino.y;
+        game_state.step_time(super::super::TICK);
+        // 20 cells a frame at most
+        assert_eq!(game_state.current_tetromino.y, y + 20);
+    }
+
+    /// Puts a tetromino of `kind` with its bounding box at `x`, `y`, turned
+    /// by `rotation` if any.
+    fn place(
+        game_state: &mut GameState,
+        kind: BlockState,
+        rotation: Option<Rotation>,
+        x: i8,
+        y: i8,
+    ) {
+        let mut tetro: CurrentTetromino = kind.into();
+        if let Some(rotation) = rotation {
+            tetro.rotate(rotation);
+        }
+        tetro.x = x;
+        tetro.y = y;
+        game_state.current_tetromino = tetro;
+    }
+
+    #[test]
+    fn tee_kicks_off_the_left_wall() {
+        let mut game_state = GameState::new(1, Rules::default());
+        // Upright against the wall, flat side out of the board
+        place(
+            &mut game_state,
+            BlockState::Tee,
+            Some(Rotation::Clockwise),
+            -1,
+            10,
+        );
+        game_state.tetromino_rotate(Rotation::Clockwise);
+
+        let tetro = &game_state.current_tetromino;
+        assert_eq!(tetro.tetromino.orientation, Orientation::Reverse);
+        assert_eq!((tetro.x, tetro.y), (0, 10));
+        assert_eq!(game_state.last_rotation, Some((Rotation::Clockwise, 1)));
+    }
+
+    #[test]
+    fn eye_kicks_into_a_well() {
+        let mut game_state = GameState::new(1, Rules::default());
+        // Four rows of stack with a well at the left wall
+        for row in &mut game_state.blocks[TOTAL_ROWS - 4..] {
+            *row = [BlockState::Gar; NUM_COLS];
+            row[0] = BlockState::Emp;
+        }
+        // Lying on the stack over the well
+        place(
+            &mut game_state,
+            BlockState::Eye,
+            None,
+            0,
+            NUM_ROWS as i8 - 6,
+        );
+        game_state.tetromino_rotate(Rotation::Clockwise);
+
+        let tetro = &game_state.current_tetromino;
+        assert_eq!(tetro.tetromino.orientation, Orientation::Right);
+        assert_eq!((tetro.x, tetro.y), (-2, NUM_ROWS as i8 - 6));
+        assert_eq!(game_state.last_rotation, Some((Rotation::Clockwise, 1)));
+        assert!(tetro.cells().all(|(x, _)| x == 0));
+    }
+
+    #[test]
+    fn tee_spin_double() {
+        let mut game_state = GameState::new(1, Rules::default());
+        // Slot at the left wall for a T pointing down, covered at one corner
+        let bottom = TOTAL_ROWS - 1;
+        game_state.blocks[bottom] = [BlockState::Gar; NUM_COLS];
+        game_state.blocks[bottom][1] = BlockState::Emp;
+        game_state.blocks[bottom - 1] = [BlockState::Gar; NUM_COLS];
+        game_state.blocks[bottom - 1][..3].fill(BlockState::Emp);
+        game_state.blocks[bottom - 2][0] = BlockState::Gar;
+        place(
+            &mut game_state,
+            BlockState::Tee,
+            Some(Rotation::Half),
+            0,
+            NUM_ROWS as i8 - 3,
+        );
+        game_state.last_rotation = Some((Rotation::Clockwise, 0));
+
+        let outcomes = game_state.press(Action::HardDrop);
+        assert!(outcomes.contains(&Outcome::LinesCleared(2)));
+        let tsd = Clear {
+            lines: 2,
+            spin: Spin::Full,
+        };
+        assert_eq!(game_state.last_clear, Some(tsd));
+        assert_eq!(game_state.score, 1200);
+    }
+
+    #[test]
+    fn block_out() {
+        let mut game_state = GameState::new(1, Rules::default());
+        // Current tetromino out of the way, stack where the next one comes in
+        game_state.current_tetromino.x = 0;
+        let next = game_state.next_tetrominos[0].clone();
+        for (x, y) in next.cells() {
+            game_state.blocks[(y + BUFFER_ROWS as i8) as usize][x as usize] = BlockState::Gar;
+        }
+
+        let outcomes = game_state.press(Action::HardDrop);
+        assert_eq!(outcomes, [Outcome::Locked, Outcome::GameOver]);
+        assert_eq!(game_state.game_over, Some(GameOverReason::BlockOut));
+    }
+
+    #[test]
+    fn lock_out() {
+        let mut game_state = GameState::new(1, Rules::default());
+        // Visible rows all taken, but for a column that keeps them from clearing
+        for row in &mut game_state.blocks[BUFFER_ROWS..] {
+            *row = [BlockState::Gar; NUM_COLS];
+            row[NUM_COLS - 1] = BlockState::Emp;
+        }
+
+        let outcomes = game_state.press(Action::HardDrop);
+        assert_eq!(outcomes, [Outcome::GameOver]);
+        assert_eq!(game_state.game_over, Some(GameOverReason::LockOut));
+        assert_eq!(game_state.pieces, 0);
+    }
+
+    #[test]
+    fn top_out() {
+        let mut game_state = GameState::new(1, Rules::default());
+        // Stack one row short of the top of the buffer zone
+        game_state.blocks[1][0] = BlockState::Gar;
+
+        let outcomes = game_state.add_garbage(1, Holes::Aligned);
+        assert_eq!(outcomes, [Outcome::GameOver]);
+        assert_eq!(game_state.game_over, Some(GameOverReason::TopOut));
+        assert!(game_state.press(Action::HardDrop).is_empty());
+    }
+
+    #[test]
+    fn garbage_pushes_the_stack_up() {
+        let mut game_state = GameState::new(1, Rules::default());
+        game_state.blocks[TOTAL_ROWS - 1][3] = BlockState::Tee;
+
+        let outcomes = game_state.add_garbage(10, Holes::Cheese);
+        assert_eq!(outcomes, [Outcome::Stepped]);
+        assert!(game_state.blocks[TOTAL_ROWS - 11][3] == BlockState::Tee);
+        assert_eq!(game_state.garbage_added, 10);
+        assert_eq!(game_state.garbage_left(), 10);
+
+        let garbage = &game_state.blocks[TOTAL_ROWS - 10..];
+        let holes: Vec<_> = garbage
+            .iter()
+            .map(|row| {
+                let holes: Vec<_> = (0..NUM_COLS)
+                    .filter(|x| row[*x] == BlockState::Emp)
+                    .collect();
+                assert_eq!(holes.len(), 1);
+                assert!(row
+                    .iter()
+                    .all(|col| matches!(col, BlockState::Emp | BlockState::Gar)));
+                holes[0]
+            })
+            .collect();
+        assert!(holes.windows(2).all(|rows| rows[0] != rows[1]));
+    }
+
+    #[test]
+    fn aligned_garbage_holes_line_up() {
+        let mut game_state = GameState::new(1, Rules::default());
+        game_state.add_garbage(4, Holes::Aligned);
+        let hole = game_state.blocks[TOTAL_ROWS - 1]
+            .iter()
+            .position(|col| *col == BlockState::Emp);
+        for row in &game_state.blocks[TOTAL_ROWS - 4..] {
+            assert_eq!(row.iter().position(|col| *col == BlockState::Emp), hole);
+        }
+    }
+
+    #[test]
+    fn garbage_pushing_out_the_top_tops_out() {
+        let mut game_state = GameState::new(1, Rules::default());
+        game_state.blocks[1][0] = BlockState::Gar;
+
+        let outcomes = game_state.add_garbage(2, Holes::Cheese);
+        assert_eq!(outcomes, [Outcome::GameOver]);
+        assert_eq!(game_state.game_over, Some(GameOverReason::TopOut));
+    }
+
+    #[test]
+    fn clock_waits_for_the_first_key() {
+        let rules = Rules {
+            mode: Mode::Sprint,
+            ..Rules::default()
+        };
+        let mut game_state = GameState::new(1, rules);
+        let spawn_y = game_state.current_tetromino.y;
+        for _ in 0..120 {
+            game_state.step_time(Duration::from_millis(16));
+        }
+        assert_eq!(game_state.current_tetromino.y, spawn_y);
+        assert_eq!(game_state.play_time(), Duration::ZERO);
+
+        game_state.press(Action::MoveLeft);
+        for _ in 0..120 {
+            game_state.step_time(Duration::from_millis(16));
+        }
+        assert!(game_state.current_tetromino.y > spawn_y);
+        assert_eq!(game_state.play_time(), Duration::from_millis(16 * 120));
+    }
+
+    fn sprint() -> GameState {
+        let rules = Rules {
+            mode: Mode::Sprint,
+            ..Rules::default()
+        };
+        GameState::new(1, rules)
+    }
+
+    #[test]
+    fn sprint_completes_at_forty_lines() {
+        let mut game_state = sprint();
+        game_state.lines = SPRINT_LINES - 2;
+        fill_bottom_row(&mut game_state);
+        game_state.press(Action::HardDrop);
+        assert_eq!(game_state.lines, SPRINT_LINES - 1);
+        assert!(!game_state.is_finished());
+
+        game_state.blocks = [[BlockState::Emp; NUM_COLS]; TOTAL_ROWS];
+        fill_bottom_row(&mut game_state);
+        let outcomes = game_state.press(Action::HardDrop);
+        assert_eq!(game_state.lines, SPRINT_LINES);
+        assert_eq!(outcomes.last(), Some(&Outcome::GameOver));
+        assert_eq!(game_state.game_over, Some(GameOverReason::Completed));
+        assert!(game_state.mode().ranked(&game_state));
+    }
+
+    #[test]
+    fn split_every_ten_lines() {
+        let mut game_state = sprint();
+        game_state.press(Action::MoveLeft);
+        wait(&mut game_state, 10, 100);
+        game_state.lines = SPLIT_LINES - 1;
+        fill_bottom_row(&mut game_state);
+        game_state.press(Action::HardDrop);
+        assert_eq!(game_state.splits, [Duration::from_secs(1)]);
+
+        // Not again until the next ten
+        wait(&mut game_state, 10, 100);
+        game_state.blocks = [[BlockState::Emp; NUM_COLS]; TOTAL_ROWS];
+        fill_bottom_row(&mut game_state);
+        game_state.press(Action::HardDrop);
+        assert_eq!(game_state.splits.len(), 1);
+    }
+
+    #[test]
+    fn ultra_ends_when_the_time_is_up() {
+        let rules = Rules {
+            mode: Mode::Ultra2,
+            ..Rules::default()
+        };
+        let mut game_state = GameState::new(1, rules);
+        // Clock doesn't run before the first key
+        wait(&mut game_state, 10, 15_000);
+        assert!(!game_state.is_finished());
+
+        game_state.press(Action::MoveLeft);
+        wait(&mut game_state, 10, 11_999);
+        assert!(!game_state.is_finished());
+        let outcomes = game_state.step_time(Duration::from_millis(10));
+        assert_eq!(outcomes, [Outcome::GameOver]);
+        assert_eq!(game_state.play_time(), Duration::from_secs(120));
+        assert_eq!(game_state.game_over, Some(GameOverReason::Completed));
+    }
+
+    fn marathon(scoring: ScoringStyle) -> GameState {
+        let rules = Rules {
+            mode: Mode::Marathon,
+            scoring,
+            ..Rules::default()
+        };
+        GameState::new(1, rules)
+    }
+
+    #[test]
+    fn marathon_wins_at_the_last_line() {
+        let mut game_state = marathon(ScoringStyle::Guideline);
+        game_state.lines = MARATHON_LINES - 1;
+        fill_bottom_row(&mut game_state);
+        game_state.press(Action::HardDrop);
+        assert_eq!(game_state.lines, MARATHON_LINES);
+        assert_eq!(game_state.game_over, Some(GameOverReason::Completed));
+        assert_eq!(game_state.mode().verdict(&game_state), "YOU WIN!");
+        // Levels stop at the top speed before the end
+        assert_eq!(game_state.level, MARATHON_LEVELS);
+    }
+
+    #[test]
+    fn simple_marathon_stays_below_the_top_speed() {
+        let mut game_state = marathon(ScoringStyle::Simple);
+        game_state.lines = MARATHON_LINES - 1;
+        fill_bottom_row(&mut game_state);
+        game_state.press(Action::HardDrop);
+        assert!(game_state.is_finished());
+        assert_eq!(game_state.level, 7);
+    }
+
+    #[test]
+    fn back_to_back_tetrises() {
+        let mut game_state = GameState::new(1, Rules::default());
+        let tetris = Clear {
+            lines: 4,
+            spin: Spin::None,
+        };
+        assert!(!game_state.update_score(tetris));
+        assert_eq!(game_state.score, 800);
+        // Half again as much, and the second clear in a row adds a combo
+        assert!(game_state.update_score(tetris));
+        assert_eq!(game_state.score, 800 + 1200 + 50);
+        assert!(game_state.back_to_back);
     }
 }
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+use winit::event::ElementState;
+
+use super::controls::Action;
+use super::input::Command;
+
+/// How far a stick is pushed before it counts as a press
+const STICK_THRESHOLD: f32 = 0.5;
+
+/// Command of a gamepad button, the layout is the same for every pad.
+fn button_command(button: Button) -> Option<Command> {
+    match button {
+        Button::DPadLeft => Some(Command::Play(Action::MoveLeft)),
+        Button::DPadRight => Some(Command::Play(Action::MoveRight)),
+        Button::DPadDown => Some(Command::Play(Action::SoftDrop)),
+        Button::DPadUp => Some(Command::Play(Action::HardDrop)),
+        Button::South => Some(Command::Play(Action::RotateClockwise)),
+        Button::East => Some(Command::Play(Action::RotateCounterClockwise)),
+        Button::North => Some(Command::Play(Action::Rotate180)),
+        Button::West | Button::LeftTrigger | Button::RightTrigger => {
+            Some(Command::Play(Action::Hold))
+        }
+        Button::Start => Some(Command::Pause),
+        Button::Select => Some(Command::Menu),
+        _ => None,
+    }
+}
+
+/// Actions the negative and positive ends of a stick axis stand for.
+fn axis_actions(axis: Axis) -> Option<(Option<Action>, Option<Action>)> {
+    match axis {
+        Axis::LeftStickX | Axis::DPadX => Some((Some(Action::MoveLeft), Some(Action::MoveRight))),
+        // Up is positive, pushing up doesn't hard drop to avoid accidents
+        Axis::LeftStickY | Axis::DPadY => Some((Some(Action::SoftDrop), None)),
+        _ => None,
+    }
+}
+
+/// Action a stick axis pushed to `value` stands for, nothing inside the dead
+/// zone.
+fn stick_action(axis: Axis, value: f32) -> Option<Action> {
+    let (negative, positive) = axis_actions(axis)?;
+    if value <= -STICK_THRESHOLD {
+        negative
+    } else if value >= STICK_THRESHOLD {
+        positive
+    } else {
+        None
+    }
+}
+
+/// Connected gamepads, turned into the same commands as the keyboard.
+pub struct Gamepads {
+    gilrs: Gilrs,
+    /// Action each stick axis is held in
+    sticks: HashMap<(GamepadId, Axis), Action>,
+}
+
+impl Gamepads {
+    /// Gamepad support, or nothing when the platform doesn't have any.
+    pub fn new() -> Option<Gamepads> {
+        match Gilrs::new() {
+            Ok(gilrs) => Some(Gamepads {
+                gilrs,
+                sticks: HashMap::new(),
+            }),
+            Err(err) => {
+                log::warn!("No gamepad support: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Commands since the last poll, with sticks pressing and releasing like
+    /// buttons.
+    pub fn poll(&mut self) -> Vec<(Command, ElementState)> {
+        let mut commands = Vec::new();
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::ButtonPressed(button, _) => {
+                    if let Some(command) = button_command(button) {
+                        commands.push((command, ElementState::Pressed));
+                    }
+                }
+                EventType::ButtonReleased(button, _) => {
+                    if let Some(command) = button_command(button) {
+                        commands.push((command, ElementState::Released));
+                    }
+                }
+                EventType::AxisChanged(axis, value, _) if axis_actions(axis).is_some() => {
+                    let pushed = stick_action(axis, value);
+                    let key = (event.id, axis);
+                    if self.sticks.get(&key).copied() != pushed {
+                        if let Some(action) = self.sticks.remove(&key) {
+                            commands.push((Command::Play(action), ElementState::Released));
+                        }
+                        if let Some(action) = pushed {
+                            self.sticks.insert(key, action);
+                            commands.push((Command::Play(action), ElementState::Pressed));
+                        }
+                    }
+                }
+                EventType::Disconnected => {
+                    // Let go of whatever the pad's sticks were holding
+                    let id = event.id;
+                    let held: Vec<_> = self
+                        .sticks
+                        .keys()
+                        .filter(|(pad, _)| *pad == id)
+                        .copied()
+                        .collect();
+                    for key in held {
+                        if let Some(action) = self.sticks.remove(&key) {
+                            commands.push((Command::Play(action), ElementState::Released));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        commands
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dpad_moves_and_drops() {
+        assert_eq!(
+            button_command(Button::DPadLeft),
+            Some(Command::Play(Action::MoveLeft))
+        );
+        assert_eq!(
+            button_command(Button::DPadRight),
+            Some(Command::Play(Action::MoveRight))
+        );
+        assert_eq!(
+            button_command(Button::DPadDown),
+            Some(Command::Play(Action::SoftDrop))
+        );
+        assert_eq!(
+            button_command(Button::DPadUp),
+            Some(Command::Play(Action::HardDrop))
+        );
+    }
+
+    #[test]
+    fn face_buttons_rotate_and_hold() {
+        assert_eq!(
+            button_command(Button::South),
+            Some(Command::Play(Action::RotateClockwise))
+        );
+        assert_eq!(
+            button_command(Button::East),
+            Some(Command::Play(Action::RotateCounterClockwise))
+        );
+        assert_eq!(
+            button_command(Button::North),
+            Some(Command::Play(Action::Rotate180))
+        );
+        assert_eq!(
+            button_command(Button::West),
+            Some(Command::Play(Action::Hold))
+        );
+        assert_eq!(
+            button_command(Button::LeftTrigger),
+            Some(Command::Play(Action::Hold))
+        );
+    }
+
+    #[test]
+    fn start_pauses() {
+        assert_eq!(button_command(Button::Start), Some(Command::Pause));
+        assert_eq!(button_command(Button::Select), Some(Command::Menu));
+        assert_eq!(button_command(Button::Mode), None);
+    }
+
+    #[test]
+    fn sticks_have_a_dead_zone() {
+        assert_eq!(stick_action(Axis::LeftStickX, 0.0), None);
+        assert_eq!(stick_action(Axis::LeftStickX, 0.4), None);
+        assert_eq!(stick_action(Axis::LeftStickX, -0.4), None);
+        assert_eq!(stick_action(Axis::LeftStickX, 0.5), Some(Action::MoveRight));
+        assert_eq!(stick_action(Axis::LeftStickX, -0.9), Some(Action::MoveLeft));
+        assert_eq!(stick_action(Axis::LeftStickY, -0.9), Some(Action::SoftDrop));
+        // Pushing up never hard drops
+        assert_eq!(stick_action(Axis::LeftStickY, 1.0), None);
+        assert_eq!(stick_action(Axis::RightStickX, 1.0), None);
+    }
+}
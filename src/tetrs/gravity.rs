@@ -0,0 +1,39 @@
+/// Gravity of one G is a cell per frame at 60 frames a second.
+pub const G: f64 = 60.0;
+/// Fastest gravity, tetrominos land as soon as they come in.
+pub const MAX_GRAVITY: f64 = 20.0 * G;
+
+/// Guideline seconds a tetromino takes to fall a row on levels 1 to 20,
+/// `(0.8 - (level - 1) * 0.007) ^ (level - 1)`. Kept as a table so that every
+/// platform falls exactly the same.
+const SECONDS_PER_ROW: [f64; 20] = [
+    1.0, 0.793, 0.6178, 0.47273, 0.3552, 0.262, 0.18968, 0.13473, 0.09388, 0.06415, 0.04298,
+    0.02822, 0.01815, 0.01144, 0.00706, 0.00426, 0.00252, 0.00146, 0.00082, 0.00046,
+];
+
+/// Gravity of the guideline curve at `level`, in cells per second.
+pub fn guideline(level: u8) -> f64 {
+    let idx = (level.max(1) as usize - 1).min(SECONDS_PER_ROW.len() - 1);
+    (1.0 / SECONDS_PER_ROW[idx]).min(MAX_GRAVITY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_cell_a_second_on_level_one() {
+        assert!((guideline(1) - 1.0).abs() < 1e-9);
+        assert_eq!(guideline(0), guideline(1));
+    }
+
+    #[test]
+    fn faster_every_level_up_to_the_cap() {
+        for level in 1..20 {
+            let (gravity, next) = (guideline(level), guideline(level + 1));
+            assert!(next > gravity || next == MAX_GRAVITY, "level {}", level);
+        }
+        assert_eq!(guideline(20), MAX_GRAVITY);
+        assert_eq!(guideline(u8::MAX), MAX_GRAVITY);
+    }
+}
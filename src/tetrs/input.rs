@@ -0,0 +1,258 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use winit::event::VirtualKeyCode;
+
+use super::controls::Action;
+
+/// Name of the bindings file, and of the local storage key in the browser
+const FILE_NAME: &str = "input";
+/// Same for the second player of a local versus game
+const PLAYER_TWO_FILE_NAME: &str = "input2";
+
+/// What a key does, either something in the game or something to the window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Play(Action),
+    Pause,
+    /// Starts a game from the title or the game over screen
+    Start,
+    Restart,
+    /// Goes back to the title screen
+    Menu,
+    /// Shows the high scores
+    Scores,
+    /// Switches to the next game mode
+    Mode,
+    /// Watches the last game played
+    Watch,
+    /// Speeds up a replay
+    Faster,
+    /// Slows down a replay
+    Slower,
+    /// Moves a paused replay on by one tick
+    StepFrame,
+    Fullscreen,
+    Quit,
+}
+
+/// Every command, in the order keys bound more than once are looked up
+const COMMANDS: [Command; 20] = [
+    Command::Play(Action::MoveLeft),
+    Command::Play(Action::MoveRight),
+    Command::Play(Action::SoftDrop),
+    Command::Play(Action::HardDrop),
+    Command::Play(Action::RotateClockwise),
+    Command::Play(Action::RotateCounterClockwise),
+    Command::Play(Action::Rotate180),
+    Command::Play(Action::Hold),
+    Command::Pause,
+    Command::Start,
+    Command::Restart,
+    Command::Menu,
+    Command::Scores,
+    Command::Mode,
+    Command::Watch,
+    Command::Faster,
+    Command::Slower,
+    Command::StepFrame,
+    Command::Fullscreen,
+    Command::Quit,
+];
+
+/// Keys bound to each command, any of them does it. Commands missing from a
+/// config file keep their default keys.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct InputMap {
+    pub move_left: Vec<VirtualKeyCode>,
+    pub move_right: Vec<VirtualKeyCode>,
+    pub soft_drop: Vec<VirtualKeyCode>,
+    pub hard_drop: Vec<VirtualKeyCode>,
+    pub rotate_clockwise: Vec<VirtualKeyCode>,
+    pub rotate_counter_clockwise: Vec<VirtualKeyCode>,
+    pub rotate_180: Vec<VirtualKeyCode>,
+    pub hold: Vec<VirtualKeyCode>,
+    pub pause: Vec<VirtualKeyCode>,
+    pub start: Vec<VirtualKeyCode>,
+    pub restart: Vec<VirtualKeyCode>,
+    pub menu: Vec<VirtualKeyCode>,
+    pub scores: Vec<VirtualKeyCode>,
+    pub mode: Vec<VirtualKeyCode>,
+    pub watch: Vec<VirtualKeyCode>,
+    pub faster: Vec<VirtualKeyCode>,
+    pub slower: Vec<VirtualKeyCode>,
+    pub step_frame: Vec<VirtualKeyCode>,
+    pub fullscreen: Vec<VirtualKeyCode>,
+    pub quit: Vec<VirtualKeyCode>,
+    /// Show the on-screen buttons once the screen is touched
+    pub touch_buttons: bool,
+    /// Clicking and dragging the mouse plays like touches do
+    pub mouse_gestures: bool,
+}
+
+impl std::default::Default for InputMap {
+    fn default() -> Self {
+        InputMap {
+            move_left: vec![VirtualKeyCode::Left],
+            move_right: vec![VirtualKeyCode::Right],
+            soft_drop: vec![VirtualKeyCode::Down],
+            hard_drop: vec![VirtualKeyCode::Space],
+            rotate_clockwise: vec![VirtualKeyCode::Up, VirtualKeyCode::X],
+            rotate_counter_clockwise: vec![VirtualKeyCode::Z],
+            rotate_180: vec![VirtualKeyCode::A],
+            hold: vec![VirtualKeyCode::C, VirtualKeyCode::LShift],
+            pause: vec![VirtualKeyCode::P],
+            start: vec![VirtualKeyCode::Return],
+            restart: vec![VirtualKeyCode::R],
+            menu: vec![VirtualKeyCode::M],
+            scores: vec![VirtualKeyCode::H],
+            mode: vec![VirtualKeyCode::Tab],
+            watch: vec![VirtualKeyCode::W],
+            faster: vec![VirtualKeyCode::Equals, VirtualKeyCode::NumpadAdd],
+            slower: vec![VirtualKeyCode::Minus, VirtualKeyCode::NumpadSubtract],
+            step_frame: vec![VirtualKeyCode::Period],
+            fullscreen: vec![VirtualKeyCode::F],
+            quit: vec![VirtualKeyCode::Escape],
+            touch_buttons: true,
+            mouse_gestures: false,
+        }
+    }
+}
+
+impl std::str::FromStr for InputMap {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s).context("Couldn't read the key bindings")
+    }
+}
+
+impl InputMap {
+    /// Command bound to a `key`, the first one in the order of the fields if
+    /// the key is bound more than once.
+    pub fn command(&self, key: VirtualKeyCode) -> Option<Command> {
+        COMMANDS
+            .into_iter()
+            .find(|command| self.keys(*command).contains(&key))
+    }
+
+    /// Keys bound to a `command`.
+    pub fn keys(&self, command: Command) -> &[VirtualKeyCode] {
+        match command {
+            Command::Play(Action::MoveLeft) => &self.move_left,
+            Command::Play(Action::MoveRight) => &self.move_right,
+            Command::Play(Action::SoftDrop) => &self.soft_drop,
+            Command::Play(Action::HardDrop) => &self.hard_drop,
+            Command::Play(Action::RotateClockwise) => &self.rotate_clockwise,
+            Command::Play(Action::RotateCounterClockwise) => &self.rotate_counter_clockwise,
+            Command::Play(Action::Rotate180) => &self.rotate_180,
+            Command::Play(Action::Hold) => &self.hold,
+            Command::Pause => &self.pause,
+            Command::Start => &self.start,
+            Command::Restart => &self.restart,
+            Command::Menu => &self.menu,
+            Command::Scores => &self.scores,
+            Command::Mode => &self.mode,
+            Command::Watch => &self.watch,
+            Command::Faster => &self.faster,
+            Command::Slower => &self.slower,
+            Command::StepFrame => &self.step_frame,
+            Command::Fullscreen => &self.fullscreen,
+            Command::Quit => &self.quit,
+        }
+    }
+
+    /// Name of the first key bound to a `command` to show on the screen.
+    pub fn key_name(&self, command: Command) -> String {
+        self.keys(command)
+            .first()
+            .map_or("-".to_string(), |key| format!("{:?}", key).to_lowercase())
+    }
+
+    /// Keys for the second player of a local versus game, around J, K and L so
+    /// they don't clash with the default layout. Only the game actions are used.
+    pub fn player_two() -> InputMap {
+        InputMap {
+            move_left: vec![VirtualKeyCode::J],
+            move_right: vec![VirtualKeyCode::L],
+            soft_drop: vec![VirtualKeyCode::K],
+            hard_drop: vec![VirtualKeyCode::I],
+            rotate_clockwise: vec![VirtualKeyCode::O],
+            rotate_counter_clockwise: vec![VirtualKeyCode::U],
+            rotate_180: vec![VirtualKeyCode::Y],
+            hold: vec![VirtualKeyCode::N],
+            ..InputMap::default()
+        }
+    }
+
+    /// Game action bound to a `key`, other commands are left out.
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        match self.command(key) {
+            Some(Command::Play(action)) => Some(action),
+            _ => None,
+        }
+    }
+
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        toml::to_string_pretty(self).context("Couldn't write the key bindings")
+    }
+
+    /// Bindings from `input.toml` in the user's config directory, or the
+    /// browser's local storage.
+    pub fn load() -> anyhow::Result<InputMap> {
+        InputMap::load_from(FILE_NAME, InputMap::default())
+    }
+
+    /// Bindings of the second player of a local versus game from `input2.toml`
+    /// next to `input.toml`.
+    pub fn load_player_two() -> anyhow::Result<InputMap> {
+        InputMap::load_from(PLAYER_TWO_FILE_NAME, InputMap::player_two())
+    }
+
+    /// Bindings from `name.toml` in the user's config directory. The `defaults`
+    /// are written there the first time so there's something to edit.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_from(name: &str, defaults: InputMap) -> anyhow::Result<InputMap> {
+        let dir = dirs::config_dir()
+            .context("Can't find the config directory")?
+            .join("tetrs");
+        let path = dir.join(format!("{}.toml", name));
+
+        if path.exists() {
+            let text = std::fs::read_to_string(&path)
+                .with_context(|| format!("Couldn't read {}", path.display()))?;
+            text.parse()
+                .with_context(|| format!("Couldn't load {}", path.display()))
+        } else {
+            let input_map = defaults;
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("Couldn't create {}", dir.display()))?;
+            std::fs::write(&path, input_map.to_toml()?)
+                .with_context(|| format!("Couldn't write {}", path.display()))?;
+            log::info!("Wrote the default key bindings to {}", path.display());
+            Ok(input_map)
+        }
+    }
+
+    /// Bindings from `tetrs.name` in the browser's local storage, stored as
+    /// TOML like on native. The `defaults` are stored the first time.
+    #[cfg(target_arch = "wasm32")]
+    fn load_from(name: &str, defaults: InputMap) -> anyhow::Result<InputMap> {
+        let storage = web_sys::window()
+            .and_then(|win| win.local_storage().ok().flatten())
+            .context("Can't access the local storage")?;
+        let key = format!("tetrs.{}", name);
+
+        match storage.get_item(&key).ok().flatten() {
+            Some(text) => text.parse(),
+            None => {
+                let input_map = defaults;
+                storage
+                    .set_item(&key, &input_map.to_toml()?)
+                    .ok()
+                    .context("Couldn't store the key bindings")?;
+                Ok(input_map)
+            }
+        }
+    }
+}
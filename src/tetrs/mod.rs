@@ -3,25 +3,69 @@ use instant::{Duration, Instant};
 #[cfg(not(target_arch = "wasm32"))]
 use std::time::{Duration, Instant};
 
+use std::cmp;
+use std::collections::HashSet;
+
 use anyhow::Context;
 use winit::{
-    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    dpi::PhysicalPosition,
+    event::{
+        ElementState, Event, KeyboardInput, MouseButton, Touch, TouchPhase, VirtualKeyCode,
+        WindowEvent,
+    },
     event_loop::{ControlFlow, EventLoop, EventLoopProxy},
     monitor::VideoMode,
     window::{Fullscreen, Window},
 };
 
-use game_state::GameState;
+use controls::Action;
+use game_state::{GameState, Outcome};
+use input::{Command, InputMap};
+use mode::Mode;
+use online::Online;
+use pointer::Pointers;
+use protocol::Message;
+use replay::{Playback, Replay};
+use rules::Rules;
 use scene::{Frame, Scene};
+use scores::{Entry, HighScores};
+use versus::Opponent;
 
-const DELTA: u64 = 17;
+/// Simulation runs in fixed steps of this long, independent of rendering
+pub const TICK: Duration = Duration::from_nanos(1_000_000_000 / 60);
+/// Most time simulated at once, after being suspended the game doesn't try to
+/// catch up with all the time that passed
+const MAX_CATCH_UP: Duration = Duration::from_millis(250);
 
-#[derive(PartialEq)]
+/// Screens of the game and how they lead into each other
+///
+/// Bootstrapped -> Title -> Running <-> Paused
+///                 ^  ^        |          |
+///                 |  |        v          |
+///                 |  +---- GameOver <----+ (restart goes back to Running)
+///                 |           |  ^
+///                 |           v  |
+///                 +------- HighScores <- EnteringName (from a game worth it)
+///
+/// Title, GameOver and HighScores can also go to Replaying, which ends in
+/// GameOver without touching the high scores. So does a versus game, once
+/// either player tops out. Online games go from Title to Waiting until the
+/// relay pairs up two players, then to Running, and from GameOver back to
+/// Waiting to play again.
+#[derive(Clone, Copy, PartialEq)]
 enum TetrsState {
     Bootstrapped,
+    Title,
     Running,
     Paused,
-    Finished,
+    GameOver,
+    /// Typing a name for the high score just made
+    EnteringName,
+    HighScores,
+    /// Watching a recorded game
+    Replaying,
+    /// Waiting for someone to play online against
+    Waiting,
 }
 
 #[derive(Debug)]
@@ -30,71 +74,674 @@ pub enum GameEvent {
     Pause,
     Fullscreen,
     Finished,
+    Quit,
 }
 
 pub struct Tetrs {
     game_state: GameState,
     scene: Scene,
     event_loop: EventLoopProxy<GameEvent>,
-    last_stepped: Instant,
+    last_update: Instant,
+    /// Time that passed but isn't simulated yet
+    accumulator: Duration,
     debug_msg: String,
     state: TetrsState,
+    input_map: InputMap,
+    /// Actions whose keys are held down, to tell presses from OS key repeats
+    held_actions: HashSet<Action>,
+    /// Actions held by touches and the mouse, apart from the keys so letting go
+    /// of one doesn't let go of the other
+    pointer_actions: HashSet<Action>,
+    #[cfg(feature = "gamepad")]
+    gamepads: Option<gamepad::Gamepads>,
+    pointers: Pointers,
+    /// Last known position of the mouse
+    cursor: PhysicalPosition<f64>,
+    mouse_down: bool,
+    /// On-screen buttons are shown, after the first touch
+    show_buttons: bool,
+    high_scores: HighScores,
+    /// Result of the last game waiting for a name to go on the table
+    pending_entry: Option<Entry>,
+    /// Name typed so far
+    name: String,
+    /// Rank of the last entry put on the table
+    highlight: Option<usize>,
+    /// Inputs of the game being played
+    recording: Option<Replay>,
+    /// Replay being watched
+    playback: Option<Playback>,
+    /// A single tick of a paused replay was asked for
+    step_frame: bool,
+    /// Keys of the second player in versus
+    player_two_input: InputMap,
+    /// Second player of the versus game being played
+    opponent: Option<Opponent>,
+    /// Connection to the relay for playing online
+    online: Option<Online>,
 }
 
 impl Tetrs {
-    pub async fn new(window: &Window, event_loop: &EventLoop<GameEvent>) -> anyhow::Result<Tetrs> {
-        let game_state = GameState::default();
+    pub async fn new(
+        window: &Window,
+        event_loop: &EventLoop<GameEvent>,
+        rules: Rules,
+        input_map: InputMap,
+    ) -> anyhow::Result<Tetrs> {
+        // Placeholder until a game is started from the title screen
+        let game_state = GameState::new(random_number::random!(), rules);
         let scene = Scene::new(window)
             .await
             .context("Couldn't create the scene")?;
 
         let event_loop = event_loop.create_proxy();
 
+        let high_scores = HighScores::load().unwrap_or_else(|err| {
+            log::warn!("Starting with no high scores: {:?}", err);
+            HighScores::default()
+        });
+        let player_two_input = InputMap::load_player_two().unwrap_or_else(|err| {
+            log::warn!("Using the default keys for player two: {:?}", err);
+            InputMap::player_two()
+        });
+
         Ok(Tetrs {
             game_state,
             scene,
             event_loop,
-            last_stepped: Instant::now(),
+            last_update: Instant::now(),
+            accumulator: Duration::ZERO,
             debug_msg: String::new(),
             state: TetrsState::Bootstrapped,
+            input_map,
+            held_actions: HashSet::new(),
+            pointer_actions: HashSet::new(),
+            #[cfg(feature = "gamepad")]
+            gamepads: gamepad::Gamepads::new(),
+            pointers: Pointers::default(),
+            cursor: PhysicalPosition::new(0.0, 0.0),
+            mouse_down: false,
+            show_buttons: false,
+            high_scores,
+            pending_entry: None,
+            name: String::new(),
+            highlight: None,
+            recording: None,
+            playback: None,
+            step_frame: false,
+            player_two_input,
+            opponent: None,
+            online: None,
         })
     }
 
-    pub fn handle_up(&mut self) {
-        self.game_state.tetromino_rotate();
-        self.render().unwrap();
+    /// Connects to the relay at `url` to play versus online in the `room`,
+    /// with whoever else joins it.
+    pub fn connect(&mut self, url: &str, room: &str) -> anyhow::Result<()> {
+        let rules = Rules {
+            mode: Mode::Versus,
+            ..self.game_state.rules.clone()
+        };
+        self.game_state = GameState::new(random_number::random!(), rules);
+        self.online = Some(Online::connect(url, room, &self.game_state.rules)?);
+        self.state = TetrsState::Waiting;
+        Ok(())
+    }
+
+    /// Keys go to the name being typed while there is one, then to the second
+    /// player in versus, otherwise they do what they are bound to.
+    pub fn handle_key(&mut self, key: VirtualKeyCode, state: ElementState) -> anyhow::Result<()> {
+        if self.state == TetrsState::EnteringName && state == ElementState::Pressed {
+            match key {
+                VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => self.confirm_name(),
+                VirtualKeyCode::Back => {
+                    self.name.pop();
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        if let (Some(opponent), Some(action)) =
+            (&mut self.opponent, self.player_two_input.action(key))
+        {
+            if self.state == TetrsState::Running || state == ElementState::Released {
+                let outcomes = opponent.handle_action(action, state);
+                self.forward_opponent(outcomes)?;
+            }
+            return Ok(());
+        }
+
+        match self.input_map.command(key) {
+            Some(command) => self.handle_command(command, state),
+            None => Ok(()),
+        }
+    }
+
+    pub fn handle_character(&mut self, character: char) {
+        if self.state == TetrsState::EnteringName
+            && !character.is_control()
+            && self.name.chars().count() < scores::MAX_NAME
+        {
+            self.name.push(character);
+        }
+    }
+
+    /// Puts the last game on the high scores under the typed name.
+    fn confirm_name(&mut self) {
+        if let Some(mut entry) = self.pending_entry.take() {
+            entry.name = match self.name.trim() {
+                "" => "-".to_string(),
+                name => name.to_string(),
+            };
+            self.highlight = self.high_scores.insert(entry, self.game_state.mode());
+            if let Err(err) = self.high_scores.save() {
+                log::warn!("Couldn't save the high scores: {:?}", err);
+            }
+        }
+        self.state = TetrsState::HighScores;
+    }
+
+    /// Does what a key or button bound to a `command` asks for.
+    pub fn handle_command(&mut self, command: Command, state: ElementState) -> anyhow::Result<()> {
+        match (state, command) {
+            (state, Command::Play(action)) => self.handle_action(action, state)?,
+            (ElementState::Pressed, Command::Pause) => match self.state {
+                // Start on a gamepad starts the game too
+                TetrsState::Title | TetrsState::GameOver | TetrsState::HighScores => {
+                    self.new_game()
+                }
+                TetrsState::EnteringName => self.confirm_name(),
+                TetrsState::Replaying => {
+                    if let Some(playback) = &mut self.playback {
+                        playback.paused = !playback.paused;
+                    }
+                }
+                _ => self.toggle_pause()?,
+            },
+            (ElementState::Pressed, Command::Start) => match self.state {
+                TetrsState::Title | TetrsState::GameOver | TetrsState::HighScores => {
+                    self.new_game()
+                }
+                TetrsState::EnteringName => self.confirm_name(),
+                _ => {}
+            },
+            (ElementState::Pressed, Command::Restart) => {
+                if matches!(
+                    self.state,
+                    TetrsState::Running
+                        | TetrsState::Paused
+                        | TetrsState::GameOver
+                        | TetrsState::Replaying
+                ) {
+                    self.new_game();
+                }
+            }
+            (ElementState::Pressed, Command::Menu) => {
+                if matches!(
+                    self.state,
+                    TetrsState::Paused
+                        | TetrsState::GameOver
+                        | TetrsState::HighScores
+                        | TetrsState::Replaying
+                        | TetrsState::Waiting
+                ) {
+                    self.save_recording();
+                    self.playback = None;
+                    self.opponent = None;
+                    self.online = None;
+                    self.state = TetrsState::Title;
+                }
+            }
+            (ElementState::Pressed, Command::Scores) => {
+                if matches!(self.state, TetrsState::Title | TetrsState::GameOver) {
+                    self.highlight = None;
+                    self.state = TetrsState::HighScores;
+                }
+            }
+            (ElementState::Pressed, Command::Mode) => {
+                if matches!(self.state, TetrsState::Title | TetrsState::HighScores) {
+                    self.switch_mode();
+                }
+            }
+            (ElementState::Pressed, Command::Watch) => {
+                if matches!(
+                    self.state,
+                    TetrsState::Title | TetrsState::GameOver | TetrsState::HighScores
+                ) {
+                    match Replay::load_last() {
+                        Ok(Some(replay)) => self.watch(replay),
+                        Ok(None) => log::info!("There is no replay to watch yet"),
+                        Err(err) => log::warn!("Couldn't load the last replay: {:?}", err),
+                    }
+                }
+            }
+            (ElementState::Pressed, Command::Faster) => {
+                if let Some(playback) = &mut self.playback {
+                    playback.faster();
+                }
+            }
+            (ElementState::Pressed, Command::Slower) => {
+                if let Some(playback) = &mut self.playback {
+                    playback.slower();
+                }
+            }
+            (ElementState::Pressed, Command::StepFrame) => {
+                if let Some(playback) = &self.playback {
+                    self.step_frame = playback.paused;
+                }
+            }
+            (ElementState::Pressed, Command::Fullscreen) => self
+                .event_loop
+                .send_event(GameEvent::Fullscreen)
+                .context("Couldn't send GameEvent::Fullscreen")?,
+            (ElementState::Pressed, Command::Quit) => self
+                .event_loop
+                .send_event(GameEvent::Quit)
+                .context("Couldn't send GameEvent::Quit")?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Handles what was pressed on the gamepads since the last poll. Returns
+    /// whether there was anything.
+    #[cfg(feature = "gamepad")]
+    pub fn poll_gamepads(&mut self) -> anyhow::Result<bool> {
+        let commands = match &mut self.gamepads {
+            Some(gamepads) => gamepads.poll(),
+            None => return Ok(false),
+        };
+        let polled = !commands.is_empty();
+        for (command, state) in commands {
+            self.handle_command(command, state)?;
+        }
+        Ok(polled)
     }
 
-    pub fn handle_down(&mut self) {
-        self.game_state.tetromino_down();
-        self.render().unwrap();
+    /// Passes a key of an `action` going down or up on to the game, and onto
+    /// the recording. Repeats are the game's business, the ones from the OS
+    /// are dropped. A replay being watched doesn't take any.
+    pub fn handle_action(&mut self, action: Action, state: ElementState) -> anyhow::Result<()> {
+        self.hold(action, state, false)
     }
 
-    pub fn handle_right(&mut self) {
-        self.game_state.tetromino_right();
-        self.render().unwrap();
+    /// Passes on an `action` held by the keys, or by a `pointer`. The game
+    /// only lets go of it once neither holds it.
+    fn hold(&mut self, action: Action, state: ElementState, pointer: bool) -> anyhow::Result<()> {
+        let (held, other) = if pointer {
+            (&mut self.pointer_actions, &self.held_actions)
+        } else {
+            (&mut self.held_actions, &self.pointer_actions)
+        };
+        let tick = self.game_state.steps_elapsed;
+        match state {
+            ElementState::Pressed => {
+                // Only presses the game took are held, so a key pressed while
+                // paused goes through once it's running again
+                if self.state == TetrsState::Running && held.insert(action) {
+                    if let Some(recording) = &mut self.recording {
+                        recording.record(tick, action, true);
+                    }
+                    if let Some(online) = &mut self.online {
+                        online.record(tick, action, true);
+                    }
+                    let outcomes = self.game_state.press(action);
+                    self.forward(outcomes)?;
+                }
+            }
+            ElementState::Released => {
+                held.remove(&action);
+                if self.playback.is_none() && !other.contains(&action) {
+                    if let Some(recording) = &mut self.recording {
+                        recording.record(tick, action, false);
+                    }
+                    // Only the game being played goes to the opponent
+                    if let Some(online) = &mut self.online {
+                        if self.state == TetrsState::Running {
+                            online.record(tick, action, false);
+                        }
+                    }
+                    self.game_state.release(action);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn handle_touch(&mut self, touch: Touch) -> anyhow::Result<()> {
+        if self.input_map.touch_buttons {
+            self.show_buttons = true;
+        }
+        self.handle_pointer(touch.id, touch.phase, touch.location)
+    }
+
+    pub fn handle_cursor(&mut self, position: PhysicalPosition<f64>) -> anyhow::Result<()> {
+        self.cursor = position;
+        if self.mouse_down {
+            self.handle_pointer(pointer::MOUSE, TouchPhase::Moved, position)?;
+        }
+        Ok(())
     }
 
-    pub fn handle_left(&mut self) {
-        self.game_state.tetromino_left();
-        self.render().unwrap();
+    /// Mouse button going down or up, dragging the mouse works like a touch.
+    pub fn handle_click(&mut self, state: ElementState) -> anyhow::Result<()> {
+        self.mouse_down = state == ElementState::Pressed;
+        let phase = match state {
+            ElementState::Pressed => TouchPhase::Started,
+            ElementState::Released => TouchPhase::Ended,
+        };
+        self.handle_pointer(pointer::MOUSE, phase, self.cursor)
+    }
+
+    fn handle_pointer(
+        &mut self,
+        id: u64,
+        phase: TouchPhase,
+        position: PhysicalPosition<f64>,
+    ) -> anyhow::Result<()> {
+        // Touching a screen other than the game moves on from it
+        if phase == TouchPhase::Started {
+            match self.state {
+                TetrsState::Title | TetrsState::GameOver | TetrsState::HighScores => {
+                    self.new_game();
+                    return Ok(());
+                }
+                TetrsState::EnteringName => {
+                    self.confirm_name();
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+
+        // Clicks on the desktop don't play unless asked for
+        if id == pointer::MOUSE && !self.input_map.mouse_gestures {
+            return Ok(());
+        }
+
+        let button = if self.show_buttons && phase == TouchPhase::Started {
+            self.scene.button_at(position)
+        } else {
+            None
+        };
+        let cell = self.scene.block_size() as f64;
+        let now = Instant::now();
+        for (action, state) in self.pointers.update(id, phase, position, button, cell, now) {
+            self.hold(action, state, true)?;
+        }
+        Ok(())
     }
 
     pub fn resize(&mut self, size: Frame) {
         self.scene.resize(&size);
     }
 
-    pub fn step_time(&mut self) -> anyhow::Result<()> {
+    /// Time only needs to be stepped while a board moves, or the relay has to
+    /// be polled.
+    pub fn is_ticking(&self) -> bool {
+        matches!(
+            self.state,
+            TetrsState::Running | TetrsState::Replaying | TetrsState::Waiting
+        ) || self.online.is_some()
+    }
+
+    /// Runs as many fixed ticks of the simulation as the time since the last
+    /// update covers. Returns when the next tick is due.
+    pub fn step_time(&mut self) -> anyhow::Result<Instant> {
         if self.state == TetrsState::Bootstrapped {
-            self.state = TetrsState::Running;
+            self.state = TetrsState::Title;
             self.event_loop.send_event(GameEvent::Fullscreen)?;
         }
-        if self.state == TetrsState::Running {
-            let delta = Duration::from_millis(DELTA);
-            if self.last_stepped.elapsed() > delta {
-                self.game_state.step_time(&self.event_loop)?;
-                self.last_stepped = Instant::now();
+
+        // Replays run faster or slower than real time
+        let speed = match &self.playback {
+            Some(playback) if playback.paused => 0.0,
+            Some(playback) => playback.speed(),
+            None => 1.0,
+        };
+        let now = Instant::now();
+        self.accumulator = cmp::min(
+            self.accumulator + now.duration_since(self.last_update).mul_f64(speed),
+            MAX_CATCH_UP,
+        );
+        self.last_update = now;
+
+        if self.step_frame {
+            self.step_frame = false;
+            self.replay_tick()?;
+        }
+        let mut ticked = false;
+        while self.accumulator >= TICK {
+            match self.state {
+                TetrsState::Running => {
+                    if let Some(online) = &mut self.online {
+                        online.take_in(&mut self.game_state);
+                    }
+                    let outcomes = self.game_state.step_time(TICK);
+                    self.forward(outcomes)?;
+                    if let Some(opponent) = &mut self.opponent {
+                        if self.state == TetrsState::Running {
+                            let outcomes = opponent.board.step_time(TICK);
+                            self.forward_opponent(outcomes)?;
+                        }
+                    }
+                    ticked = true;
+                }
+                TetrsState::Replaying => self.replay_tick()?,
+                _ => {}
             }
+            self.accumulator -= TICK;
+        }
+        if let Some(online) = &mut self.online {
+            if ticked && self.state == TetrsState::Running {
+                if let Err(err) = online.send_frame(&self.game_state) {
+                    self.stop_online(err);
+                }
+            }
+        }
+        // Clock in the side panel keeps going even if nothing moves
+        if ticked && self.game_state.mode().clock() {
+            self.event_loop
+                .send_event(GameEvent::Step)
+                .context("Couldn't send GameEvent::Step")?;
+        }
+
+        Ok(now + (TICK - self.accumulator))
+    }
+
+    /// Feeds the replay's inputs due before the next tick to the game, then
+    /// runs the tick, same as they went when the game was played.
+    fn replay_tick(&mut self) -> anyhow::Result<()> {
+        if self.state != TetrsState::Replaying {
+            return Ok(());
+        }
+        let inputs = match &mut self.playback {
+            Some(playback) => playback.due(self.game_state.steps_elapsed),
+            None => return Ok(()),
+        };
+        for replay::Input(_, action, pressed) in inputs {
+            if pressed {
+                let outcomes = self.game_state.press(action);
+                self.forward(outcomes)?;
+            } else {
+                self.game_state.release(action);
+            }
+        }
+        let outcomes = self.game_state.step_time(TICK);
+        self.forward(outcomes)?;
+        // Paused replays don't step on their own, redraw the one asked for
+        self.event_loop
+            .send_event(GameEvent::Step)
+            .context("Couldn't send GameEvent::Step")
+    }
+
+    /// Passes what happened in the game on to the event loop.
+    fn forward(&mut self, outcomes: Vec<Outcome>) -> anyhow::Result<()> {
+        for outcome in outcomes {
+            match outcome {
+                Outcome::Stepped | Outcome::Held | Outcome::Locked | Outcome::LinesCleared(_) => {
+                    self.event_loop
+                        .send_event(GameEvent::Step)
+                        .context("Couldn't send GameEvent::Step")?
+                }
+                Outcome::Attack(rows) => {
+                    if let Some(opponent) = &mut self.opponent {
+                        opponent.board.receive(rows);
+                    }
+                    if let Some(online) = &mut self.online {
+                        if let Err(err) = online.send(&Message::Attack { rows }) {
+                            self.stop_online(err);
+                        }
+                    }
+                }
+                Outcome::GameOver if self.online.is_some() => {
+                    if let Some(online) = &mut self.online {
+                        let sent = online
+                            .send_frame(&self.game_state)
+                            .and_then(|_| online.send(&Message::GameOver));
+                        if let Err(err) = sent {
+                            self.stop_online(err);
+                            continue;
+                        }
+                        online.result = Some("YOU LOSE!");
+                    }
+                    self.state = TetrsState::GameOver;
+                    self.event_loop
+                        .send_event(GameEvent::Finished)
+                        .context("Couldn't send GameEvent::Finished")?
+                }
+                Outcome::GameOver
+                    if self.state == TetrsState::Replaying || self.opponent.is_some() =>
+                {
+                    self.state = TetrsState::GameOver;
+                    self.event_loop
+                        .send_event(GameEvent::Finished)
+                        .context("Couldn't send GameEvent::Finished")?
+                }
+                Outcome::GameOver => {
+                    self.save_recording();
+                    let entry = Entry::new(&self.game_state);
+                    let mode = self.game_state.mode();
+                    self.state = if mode.ranked(&self.game_state)
+                        && self.high_scores.qualifies(&entry, mode)
+                    {
+                        self.name = self.high_scores.last_name.clone();
+                        self.pending_entry = Some(entry);
+                        TetrsState::EnteringName
+                    } else {
+                        TetrsState::GameOver
+                    };
+                    self.event_loop
+                        .send_event(GameEvent::Finished)
+                        .context("Couldn't send GameEvent::Finished")?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Same as `forward` for the second player's board, their attacks go to
+    /// the first player.
+    fn forward_opponent(&mut self, outcomes: Vec<Outcome>) -> anyhow::Result<()> {
+        for outcome in outcomes {
+            match outcome {
+                Outcome::Attack(rows) => self.game_state.receive(rows),
+                Outcome::GameOver => {
+                    self.state = TetrsState::GameOver;
+                    self.event_loop
+                        .send_event(GameEvent::Finished)
+                        .context("Couldn't send GameEvent::Finished")?
+                }
+                _ => self
+                    .event_loop
+                    .send_event(GameEvent::Step)
+                    .context("Couldn't send GameEvent::Step")?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Gives up on the online game after the relay couldn't be reached, back
+    /// to the title screen.
+    fn stop_online(&mut self, err: anyhow::Error) {
+        log::warn!("Stopped playing online: {:?}", err);
+        self.online = None;
+        self.state = TetrsState::Title;
+    }
+
+    /// Takes in what the relay sent. Returns if anything came, to redraw.
+    pub fn poll_network(&mut self) -> anyhow::Result<bool> {
+        let messages = match self.online.as_mut().map(|online| online.poll()) {
+            Some(Ok(messages)) => messages,
+            Some(Err(err)) => {
+                self.stop_online(err);
+                return Ok(true);
+            }
+            None => return Ok(false),
+        };
+        let polled = !messages.is_empty();
+        for message in messages {
+            self.handle_message(message)?;
+        }
+        Ok(polled)
+    }
+
+    fn handle_message(&mut self, message: Message) -> anyhow::Result<()> {
+        let online = match &mut self.online {
+            Some(online) => online,
+            None => return Ok(()),
+        };
+        match message {
+            Message::Start {
+                seed,
+                opponent_seed,
+                rules,
+            } => {
+                self.game_state = GameState::new(seed, rules.clone());
+                log::info!(
+                    "Playing online with seed {} against seed {}",
+                    seed,
+                    opponent_seed
+                );
+                online.start(opponent_seed, rules);
+                self.recording = None;
+                self.playback = None;
+                self.opponent = None;
+                self.held_actions.clear();
+                self.pointer_actions.clear();
+                self.accumulator = Duration::ZERO;
+                self.last_update = Instant::now();
+                self.state = TetrsState::Running;
+            }
+            Message::Frame {
+                tick,
+                inputs,
+                garbage,
+            } => {
+                if let Some(mirror) = &mut online.mirror {
+                    mirror.frame(tick, inputs, garbage);
+                }
+            }
+            Message::Attack { rows } if self.state == TetrsState::Running => {
+                online.attacked(rows);
+            }
+            Message::GameOver | Message::Left if self.state == TetrsState::Running => {
+                online.result = Some(match message {
+                    Message::Left => "OPPONENT LEFT",
+                    _ => "YOU WIN!",
+                });
+                self.state = TetrsState::GameOver;
+                self.event_loop
+                    .send_event(GameEvent::Finished)
+                    .context("Couldn't send GameEvent::Finished")?
+            }
+            Message::Refused { reason } => {
+                log::warn!("The relay refused: {}", reason);
+                self.online = None;
+                self.state = TetrsState::Title;
+            }
+            _ => {}
         }
         Ok(())
     }
@@ -104,20 +751,116 @@ impl Tetrs {
         self.debug_msg = msg;
     }
 
+    /// Online games don't stop for one of the players.
     pub fn toggle_pause(&mut self) -> anyhow::Result<()> {
-        if self.state == TetrsState::Running {
-            self.state = TetrsState::Paused;
-            self.event_loop.send_event(GameEvent::Pause)?;
-        } else {
-            self.state = TetrsState::Running;
+        if self.online.is_some() {
+            return Ok(());
+        }
+        match self.state {
+            TetrsState::Running => {
+                self.state = TetrsState::Paused;
+                self.event_loop.send_event(GameEvent::Pause)?;
+            }
+            TetrsState::Paused => {
+                self.state = TetrsState::Running;
+                // Time paused isn't caught up on
+                self.accumulator = Duration::ZERO;
+                self.last_update = Instant::now();
+            }
+            _ => {}
         }
         Ok(())
     }
 
-    pub fn finish_game(&mut self) -> anyhow::Result<()> {
-        self.state = TetrsState::Finished;
-        self.render().context("Can't render after finish")?;
-        Ok(())
+    /// Starts over with a fresh board and a new seed, keeping the rules. The
+    /// scene and everything under it stay as they are. Versus games get a
+    /// second board and aren't recorded, a replay only has one player's keys.
+    /// Online, a game that ended waits for the next one in the same room.
+    pub fn new_game(&mut self) {
+        if let Some(online) = &mut self.online {
+            if self.state != TetrsState::Running {
+                if let Err(err) = online.join(&self.game_state.rules) {
+                    self.stop_online(err);
+                } else {
+                    self.state = TetrsState::Waiting;
+                }
+            }
+            return;
+        }
+        self.save_recording();
+        let rules = self.game_state.rules.clone();
+        self.game_state = GameState::new(random_number::random!(), rules);
+        log::info!("Starting the game with seed {}", self.game_state.seed);
+        if self.game_state.rules.mode == Mode::Versus {
+            self.opponent = Some(Opponent::new(self.game_state.rules.clone()));
+            self.recording = None;
+        } else {
+            self.opponent = None;
+            self.recording = Some(Replay::new(
+                self.game_state.seed,
+                self.game_state.rules.clone(),
+            ));
+        }
+        self.playback = None;
+        // Keys held from before would swallow their first press in this game
+        self.held_actions.clear();
+        self.pointer_actions.clear();
+        self.accumulator = Duration::ZERO;
+        self.last_update = Instant::now();
+        self.state = TetrsState::Running;
+    }
+
+    /// Saves the game being recorded, finished or not, to be watched later.
+    /// Games without a single key pressed aren't worth it.
+    pub fn save_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            if recording.inputs.is_empty() {
+                return;
+            }
+            if let Err(err) = recording.save() {
+                log::warn!("Couldn't save the replay: {:?}", err);
+            }
+        }
+    }
+
+    /// Moves on to the next mode for the games to come, the high scores shown
+    /// follow it.
+    fn switch_mode(&mut self) {
+        let mut rules = self.game_state.rules.clone();
+        rules.mode = rules.mode.next();
+        self.game_state = GameState::new(random_number::random!(), rules);
+        self.opponent = None;
+        self.highlight = None;
+    }
+
+    /// Plays a recorded game again from the start, with the rules it was
+    /// played with.
+    pub fn watch(&mut self, replay: Replay) {
+        let playback = Playback::new(replay);
+        self.game_state = GameState::new(playback.seed(), playback.rules());
+        log::info!("Watching the game with seed {}", self.game_state.seed);
+        self.recording = None;
+        self.opponent = None;
+        self.online = None;
+        self.playback = Some(playback);
+        self.step_frame = false;
+        self.accumulator = Duration::ZERO;
+        self.last_update = Instant::now();
+        self.state = TetrsState::Replaying;
+    }
+
+    /// Key of a `command` with what it does, to show on the screen.
+    fn hint(&self, command: Command, text: &str) -> String {
+        format!("{}  {}", self.input_map.key_name(command), text)
+    }
+
+    /// Best result of the mode being played, kept on the high scores.
+    fn personal_best(&self) -> String {
+        let mode = self.game_state.mode();
+        match self.high_scores.table(&mode.name()).next() {
+            Some(best) => format!("best  {}", mode.headline(best)),
+            None => "no best yet".to_string(),
+        }
     }
 
     pub fn render(&mut self) -> anyhow::Result<()> {
@@ -126,13 +869,133 @@ impl Tetrs {
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
-        if self.state != TetrsState::Finished {
-            self.scene.game_scene(&view, &self.game_state);
-            if self.state == TetrsState::Paused {
-                self.scene.render_pause(&view);
+        // Board of the other player, next to this one in or after a versus game
+        let other = match (&self.opponent, &self.online) {
+            (Some(opponent), _) => Some(&opponent.board),
+            (None, Some(online)) => online.mirror.as_ref().map(|mirror| &mirror.board),
+            (None, None) => None,
+        }
+        .filter(|_| {
+            matches!(
+                self.state,
+                TetrsState::Running | TetrsState::Paused | TetrsState::GameOver
+            )
+        });
+        self.scene.set_arenas(if other.is_some() { 2 } else { 1 });
+
+        match (self.state, other) {
+            (TetrsState::Running | TetrsState::Paused | TetrsState::GameOver, Some(other)) => {
+                self.scene.versus_scene(&view, [&self.game_state, other]);
+                if self.state == TetrsState::Paused {
+                    let hints = [
+                        self.hint(Command::Restart, "restart"),
+                        self.hint(Command::Menu, "menu"),
+                    ];
+                    self.scene.render_pause(&view, &hints);
+                } else if self.state == TetrsState::GameOver {
+                    let hints = [
+                        self.hint(Command::Start, "play again"),
+                        self.hint(Command::Menu, "menu"),
+                    ];
+                    let result = match &self.online {
+                        Some(online) => online.result.unwrap_or_default(),
+                        None => versus::winner(&self.game_state),
+                    };
+                    self.scene.render_banner(&view, result, &hints);
+                }
+            }
+            (TetrsState::Waiting, _) => {
+                let hints = [
+                    match &self.online {
+                        Some(online) => format!("waiting in room {}", online.room),
+                        None => String::new(),
+                    },
+                    self.hint(Command::Menu, "leave"),
+                ];
+                self.scene.title_scene(&view, &hints);
+            }
+            (TetrsState::Bootstrapped | TetrsState::Title, _) => {
+                let hints = [
+                    self.hint(
+                        Command::Mode,
+                        &format!("mode  {}", self.game_state.mode().name()),
+                    ),
+                    self.hint(Command::Start, "play"),
+                    self.hint(Command::Scores, "high scores"),
+                    self.hint(Command::Watch, "watch last game"),
+                ];
+                self.scene.title_scene(&view, &hints);
+            }
+            (TetrsState::Running | TetrsState::Paused, _) => {
+                self.scene.game_scene(&view, &self.game_state);
+                if self.state == TetrsState::Paused {
+                    let hints = [
+                        self.hint(Command::Restart, "restart"),
+                        self.hint(Command::Menu, "menu"),
+                    ];
+                    self.scene.render_pause(&view, &hints);
+                }
+                if self.show_buttons {
+                    self.scene.render_buttons(&view);
+                }
+            }
+            (TetrsState::GameOver, _) => {
+                let hints = [
+                    self.personal_best(),
+                    self.hint(Command::Start, "play again"),
+                    self.hint(Command::Scores, "high scores"),
+                    self.hint(Command::Menu, "menu"),
+                ];
+                self.scene.finish_scene(&view, &self.game_state, &hints);
+            }
+            (TetrsState::Replaying, _) => {
+                self.scene.game_scene(&view, &self.game_state);
+                if let Some(playback) = &self.playback {
+                    let status = if playback.paused {
+                        "paused".to_string()
+                    } else {
+                        format!("{}x", playback.speed())
+                    };
+                    let lines = [
+                        status,
+                        format!(
+                            "{}/{}  speed",
+                            self.input_map.key_name(Command::Slower),
+                            self.input_map.key_name(Command::Faster)
+                        ),
+                        self.hint(Command::Pause, "pause"),
+                        self.hint(Command::StepFrame, "step"),
+                    ];
+                    self.scene.render_replay(&view, &lines);
+                }
+            }
+            (TetrsState::EnteringName, _) => {
+                let mode = self.game_state.mode();
+                let best = self.high_scores.table(&mode.name()).next();
+                let headline = match (&self.pending_entry, best) {
+                    (Some(entry), Some(best)) if !mode.ranks_above(entry, best) => {
+                        "new high score!"
+                    }
+                    _ => "new personal best!",
+                };
+                let hints = [
+                    headline.to_string(),
+                    format!("name  {}_", self.name),
+                    self.hint(Command::Start, "save"),
+                ];
+                self.scene.finish_scene(&view, &self.game_state, &hints);
+            }
+            (TetrsState::HighScores, _) => {
+                let mode = self.game_state.mode();
+                let entries: Vec<_> = self.high_scores.table(&mode.name()).collect();
+                let hints = [
+                    self.hint(Command::Start, "play"),
+                    self.hint(Command::Mode, "next mode"),
+                    self.hint(Command::Menu, "menu"),
+                ];
+                self.scene
+                    .scores_scene(&view, mode, &entries, self.highlight, &hints);
             }
-        } else {
-            self.scene.finish_scene(&view, &self.game_state);
         }
 
         self.scene.render_debug(&view, &self.debug_msg);
@@ -191,67 +1054,128 @@ pub async fn run(
     #[cfg(not(target_arch = "wasm32"))]
     let fullscreen_mode = maybe_mode.context("Can't obtain max size mode for fullscreen")?;
 
-    event_loop.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Poll;
-
-        tetrs.step_time().expect("Panicked while stepping time");
-
-        match event {
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::Resized(size) => {
-                    tetrs.resize(size);
-                    window.request_redraw();
-                }
-                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                WindowEvent::KeyboardInput {
-                    input:
-                        KeyboardInput {
-                            virtual_keycode: Some(virtual_code),
-                            state: ElementState::Pressed,
-                            ..
-                        },
-                    ..
-                } => match virtual_code {
-                    #[cfg(not(target_arch = "wasm32"))]
-                    VirtualKeyCode::Escape => *control_flow = ControlFlow::Exit,
-                    VirtualKeyCode::Up => tetrs.handle_up(),
-                    VirtualKeyCode::Down => tetrs.handle_down(),
-                    VirtualKeyCode::Left => tetrs.handle_left(),
-                    VirtualKeyCode::Right => tetrs.handle_right(),
-                    VirtualKeyCode::Space => {
-                        tetrs.toggle_pause().expect("Panicked while toggling pause")
-                    }
-                    VirtualKeyCode::F => {
-                        #[cfg(not(target_arch = "wasm32"))]
-                        toggle_fullscreen(&window, &fullscreen_mode);
-                    }
-                    _ => {}
-                },
-                _ => {}
-            },
-            Event::RedrawRequested(_) => {
-                tetrs.render().expect("Panicked while render");
+    event_loop.run(move |event, _, control_flow| match event {
+        Event::WindowEvent { event, .. } => match event {
+            WindowEvent::Resized(size) => {
+                tetrs.resize(size);
+                window.request_redraw();
+            }
+            WindowEvent::CloseRequested => {
+                tetrs.save_recording();
+                *control_flow = ControlFlow::Exit
             }
-            Event::UserEvent(GameEvent::Step | GameEvent::Pause) => {
-                tetrs.render().expect("Panicked while render");
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(virtual_code),
+                        state,
+                        ..
+                    },
+                ..
+            } => {
+                tetrs
+                    .handle_key(virtual_code, state)
+                    .expect("Panicked while handling a key");
+                window.request_redraw();
             }
-            Event::UserEvent(GameEvent::Fullscreen) => {
-                #[cfg(not(target_arch = "wasm32"))]
-                toggle_fullscreen(&window, &fullscreen_mode);
+            WindowEvent::ReceivedCharacter(character) => {
+                tetrs.handle_character(character);
+                window.request_redraw();
             }
-            Event::UserEvent(GameEvent::Finished) => {
-                tetrs.finish_game().unwrap();
+            WindowEvent::Touch(touch) => {
+                tetrs
+                    .handle_touch(touch)
+                    .expect("Panicked while handling a touch");
+                window.request_redraw();
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                tetrs
+                    .handle_cursor(position)
+                    .expect("Panicked while handling the mouse");
+                window.request_redraw();
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                tetrs
+                    .handle_click(state)
+                    .expect("Panicked while handling the mouse");
+                window.request_redraw();
             }
             _ => {}
+        },
+        Event::MainEventsCleared => {
+            #[cfg(feature = "gamepad")]
+            if tetrs
+                .poll_gamepads()
+                .expect("Panicked while handling a gamepad")
+            {
+                window.request_redraw();
+            }
+            if tetrs
+                .poll_network()
+                .expect("Panicked while talking to the relay")
+            {
+                window.request_redraw();
+            }
+            let next_tick = tetrs.step_time().expect("Panicked while stepping time");
+            // Gamepads are polled, they can't wake the loop up
+            if tetrs.is_ticking() || cfg!(feature = "gamepad") {
+                control_flow.set_wait_until(next_tick);
+            } else {
+                control_flow.set_wait();
+            }
+        }
+        Event::RedrawRequested(_) => {
+            tetrs.render().expect("Panicked while render");
         }
+        Event::UserEvent(GameEvent::Step | GameEvent::Pause) => {
+            window.request_redraw();
+        }
+        Event::UserEvent(GameEvent::Fullscreen) => {
+            #[cfg(not(target_arch = "wasm32"))]
+            toggle_fullscreen(&window, &fullscreen_mode);
+        }
+        Event::UserEvent(GameEvent::Finished) => {
+            window.request_redraw();
+        }
+        Event::UserEvent(GameEvent::Quit) => {
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                tetrs.save_recording();
+                *control_flow = ControlFlow::Exit
+            }
+        }
+        _ => {}
     });
 }
 
+pub mod attack;
 mod base;
-mod colours;
+pub mod colours;
+pub mod controls;
 mod drawable;
-mod game_state;
+pub mod game_state;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+pub mod gravity;
+pub mod input;
+pub mod mode;
+pub mod online;
+mod pointer;
+pub mod protocol;
+pub mod randomizer;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod relay;
+pub mod replay;
+pub mod rotation;
+pub mod rules;
 mod scene;
-mod tetromino;
+pub mod scores;
+pub mod scoring;
+pub mod tetromino;
+pub mod versus;
 mod vertex;
 mod writer;
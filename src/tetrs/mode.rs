@@ -0,0 +1,615 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::game_state::{GameOverReason, GameState, Holes, NUM_ROWS, SPLIT_LINES};
+use super::rules::Rules;
+use super::scores::Entry;
+
+/// Lines to clear in a sprint
+pub const SPRINT_LINES: u32 = 40;
+/// Lines to clear to win a marathon
+pub const MARATHON_LINES: u32 = 150;
+/// Level a marathon stops speeding up at
+pub const MARATHON_LEVELS: u8 = 15;
+/// Most rows of garbage the dig modes keep, half of the board
+pub const MAX_GARBAGE_ROWS: u8 = (NUM_ROWS / 2) as u8;
+
+/// Goal of a game, how it's ranked and what's shown about it.
+pub trait GameMode {
+    /// Name the high scores are kept under
+    fn name(&self) -> String;
+    /// Goal is reached, checked after every lock and every step of time
+    fn completed(&self, _game_state: &GameState) -> bool {
+        false
+    }
+    /// Highest level the game goes up to
+    fn max_level(&self) -> u8 {
+        u8::MAX
+    }
+    /// Garbage rows to push up into the board and their holes, asked for when
+    /// the game starts and after every lock
+    fn garbage(&self, _game_state: &GameState) -> Option<(usize, Holes)> {
+        None
+    }
+    /// A finished game goes on the high scores
+    fn ranked(&self, game_state: &GameState) -> bool;
+    /// `entry` goes above `other` on the high scores
+    fn ranks_above(&self, entry: &Entry, other: &Entry) -> bool;
+    /// Name of what the high scores are ranked by
+    fn ranked_by(&self) -> &'static str;
+    /// Value of an `entry` the high scores are ranked by
+    fn headline(&self, entry: &Entry) -> String;
+    /// Lines under the stats in the side panel
+    fn panel(&self, game_state: &GameState) -> Vec<String>;
+    /// Side panel has a clock, it has to be redrawn every tick
+    fn clock(&self) -> bool {
+        false
+    }
+    /// Title of the finish screen
+    fn verdict(&self, _game_state: &GameState) -> &'static str {
+        "FINISHED!"
+    }
+    /// Stats on the finish screen
+    fn results(&self, game_state: &GameState) -> Vec<String>;
+}
+
+/// Available game modes.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Play until topping out, speed goes up with the level
+    Endless,
+    /// Clear 150 lines over 15 levels
+    Marathon,
+    /// Clear 40 lines as fast as possible
+    Sprint,
+    /// Score as much as possible in two minutes
+    Ultra2,
+    /// Score as much as possible in three minutes
+    Ultra3,
+    /// Clear rows of garbage the board starts with
+    Dig,
+    /// Garbage keeps coming back up to the same height
+    Survival,
+    /// Two boards side by side sending each other garbage
+    Versus,
+}
+
+impl std::default::Default for Mode {
+    fn default() -> Self {
+        Mode::Endless
+    }
+}
+
+impl std::str::FromStr for Mode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "endless" => Ok(Mode::Endless),
+            "marathon" => Ok(Mode::Marathon),
+            "sprint" => Ok(Mode::Sprint),
+            "ultra2" => Ok(Mode::Ultra2),
+            "ultra" | "ultra3" => Ok(Mode::Ultra3),
+            "dig" => Ok(Mode::Dig),
+            "survival" => Ok(Mode::Survival),
+            "versus" => Ok(Mode::Versus),
+            _ => Err(anyhow::Error::msg(format!("Unknown mode {}", s))),
+        }
+    }
+}
+
+impl Mode {
+    /// The mode, with its garbage set up by the `rules`.
+    pub fn game_mode(&self, rules: &Rules) -> Box<dyn GameMode> {
+        let garbage = Garbage {
+            rows: rules.garbage_rows.clamp(1, MAX_GARBAGE_ROWS) as usize,
+            holes: if rules.messy_garbage {
+                Holes::Messy
+            } else {
+                Holes::Cheese
+            },
+        };
+        match self {
+            Mode::Endless => Box::new(Endless),
+            Mode::Marathon => Box::new(Marathon),
+            Mode::Sprint => Box::new(Sprint),
+            Mode::Ultra2 => Box::new(Ultra { minutes: 2 }),
+            Mode::Ultra3 => Box::new(Ultra { minutes: 3 }),
+            Mode::Dig => Box::new(Dig(garbage)),
+            Mode::Survival => Box::new(Survival(garbage)),
+            Mode::Versus => Box::new(Versus),
+        }
+    }
+
+    /// Mode after this one, to cycle through them on the title screen.
+    pub fn next(&self) -> Mode {
+        match self {
+            Mode::Endless => Mode::Marathon,
+            Mode::Marathon => Mode::Sprint,
+            Mode::Sprint => Mode::Ultra2,
+            Mode::Ultra2 => Mode::Ultra3,
+            Mode::Ultra3 => Mode::Dig,
+            Mode::Dig => Mode::Survival,
+            Mode::Survival => Mode::Versus,
+            Mode::Versus => Mode::Endless,
+        }
+    }
+}
+
+/// Duration as minutes, seconds and hundredths. Play time goes up a tick, a
+/// 60th of a second, at a time, so it's rounded to the nearest hundredth and
+/// the last digit skips a value now and then.
+pub fn precise_time(duration: Duration) -> String {
+    let centis = (duration.as_micros() + 5_000) / 10_000;
+    format!(
+        "{}:{:02}.{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+/// Classic marathon without an end, ranked by score.
+pub struct Endless;
+
+impl GameMode for Endless {
+    fn name(&self) -> String {
+        "endless".to_string()
+    }
+
+    fn ranked(&self, _game_state: &GameState) -> bool {
+        true
+    }
+
+    fn ranks_above(&self, entry: &Entry, other: &Entry) -> bool {
+        entry.score > other.score
+    }
+
+    fn ranked_by(&self) -> &'static str {
+        "score"
+    }
+
+    fn headline(&self, entry: &Entry) -> String {
+        entry.score.to_string()
+    }
+
+    fn panel(&self, _game_state: &GameState) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn results(&self, game_state: &GameState) -> Vec<String> {
+        vec![format!("SCORE  {}", game_state.score)]
+    }
+}
+
+/// 150 lines with the speed topping out at level 15, clearing them all wins.
+/// Ranked by score whether it's won or not. Simple scoring only goes up a level
+/// every 20 lines, so it finishes on level 7 without reaching the top speed.
+pub struct Marathon;
+
+impl GameMode for Marathon {
+    fn name(&self) -> String {
+        "marathon".to_string()
+    }
+
+    fn completed(&self, game_state: &GameState) -> bool {
+        game_state.lines >= MARATHON_LINES
+    }
+
+    fn max_level(&self) -> u8 {
+        MARATHON_LEVELS
+    }
+
+    fn ranked(&self, _game_state: &GameState) -> bool {
+        true
+    }
+
+    fn ranks_above(&self, entry: &Entry, other: &Entry) -> bool {
+        entry.score > other.score
+    }
+
+    fn ranked_by(&self) -> &'static str {
+        "score"
+    }
+
+    fn headline(&self, entry: &Entry) -> String {
+        entry.score.to_string()
+    }
+
+    fn panel(&self, game_state: &GameState) -> Vec<String> {
+        vec![format!(
+            "left    {}",
+            MARATHON_LINES.saturating_sub(game_state.lines)
+        )]
+    }
+
+    fn verdict(&self, game_state: &GameState) -> &'static str {
+        if game_state.game_over == Some(GameOverReason::Completed) {
+            "YOU WIN!"
+        } else {
+            "FINISHED!"
+        }
+    }
+
+    fn results(&self, game_state: &GameState) -> Vec<String> {
+        vec![
+            format!("SCORE  {}", game_state.score),
+            format!("LINES  {}/{}", game_state.lines, MARATHON_LINES),
+            format!("LEVEL  {}", game_state.level),
+            format!("TIME  {}", precise_time(game_state.play_time())),
+        ]
+    }
+}
+
+/// 40 lines against the clock, which starts with the first input, with a split
+/// time every 10. Only finished sprints are ranked, fastest first.
+pub struct Sprint;
+
+impl GameMode for Sprint {
+    fn name(&self) -> String {
+        "sprint".to_string()
+    }
+
+    fn completed(&self, game_state: &GameState) -> bool {
+        game_state.lines >= SPRINT_LINES
+    }
+
+    fn ranked(&self, game_state: &GameState) -> bool {
+        game_state.game_over == Some(GameOverReason::Completed)
+    }
+
+    fn ranks_above(&self, entry: &Entry, other: &Entry) -> bool {
+        entry.duration < other.duration
+    }
+
+    fn ranked_by(&self) -> &'static str {
+        "time"
+    }
+
+    fn headline(&self, entry: &Entry) -> String {
+        precise_time(entry.duration)
+    }
+
+    fn panel(&self, game_state: &GameState) -> Vec<String> {
+        let mut panel = vec![
+            format!("left    {}", SPRINT_LINES.saturating_sub(game_state.lines)),
+            format!("time    {}", precise_time(game_state.play_time())),
+        ];
+        if let Some(split) = game_state.splits.last() {
+            panel.push(format!("split   {}", precise_time(*split)));
+        }
+        panel
+    }
+
+    fn clock(&self) -> bool {
+        true
+    }
+
+    fn verdict(&self, game_state: &GameState) -> &'static str {
+        if game_state.game_over == Some(GameOverReason::Completed) {
+            "CLEARED!"
+        } else {
+            "FINISHED!"
+        }
+    }
+
+    fn results(&self, game_state: &GameState) -> Vec<String> {
+        let secs = game_state.play_time().as_secs_f64();
+        let pieces = game_state.pieces as f64;
+        let per_second = if secs > 0.0 { pieces / secs } else { 0.0 };
+        let keys_per_piece = if pieces > 0.0 {
+            game_state.key_presses as f64 / pieces
+        } else {
+            0.0
+        };
+        let mut results = vec![
+            format!("TIME  {}", precise_time(game_state.play_time())),
+            format!("PIECES  {}", game_state.pieces),
+            format!("PPS  {:.2}", per_second),
+            format!("KPP  {:.2}", keys_per_piece),
+        ];
+        for (idx, split) in game_state.splits.iter().enumerate() {
+            let lines = (idx as u32 + 1) * SPLIT_LINES;
+            results.push(format!("{}  {}", lines, precise_time(*split)));
+        }
+        results
+    }
+}
+
+/// Score attack against a clock that starts with the first input, ranked by
+/// score. Topping out early ends it too, with what was scored so far.
+pub struct Ultra {
+    pub minutes: u64,
+}
+
+impl Ultra {
+    fn limit(&self) -> Duration {
+        Duration::from_secs(self.minutes * 60)
+    }
+}
+
+impl GameMode for Ultra {
+    fn name(&self) -> String {
+        format!("ultra {}m", self.minutes)
+    }
+
+    fn completed(&self, game_state: &GameState) -> bool {
+        game_state.play_time() >= self.limit()
+    }
+
+    fn ranked(&self, _game_state: &GameState) -> bool {
+        true
+    }
+
+    fn ranks_above(&self, entry: &Entry, other: &Entry) -> bool {
+        entry.score > other.score
+    }
+
+    fn ranked_by(&self) -> &'static str {
+        "score"
+    }
+
+    fn headline(&self, entry: &Entry) -> String {
+        entry.score.to_string()
+    }
+
+    fn panel(&self, game_state: &GameState) -> Vec<String> {
+        let left = self.limit().saturating_sub(game_state.play_time());
+        vec![format!("time    {}", precise_time(left))]
+    }
+
+    fn clock(&self) -> bool {
+        true
+    }
+
+    fn verdict(&self, game_state: &GameState) -> &'static str {
+        if game_state.game_over == Some(GameOverReason::Completed) {
+            "TIME UP!"
+        } else {
+            "FINISHED!"
+        }
+    }
+
+    fn results(&self, game_state: &GameState) -> Vec<String> {
+        let minutes = game_state.play_time().as_secs_f64() / 60.0;
+        let per_minute = if minutes > 0.0 {
+            game_state.score as f64 / minutes
+        } else {
+            0.0
+        };
+        vec![
+            format!("SCORE  {}", game_state.score),
+            format!("LINES  {}", game_state.lines),
+            format!("SCORE/MIN  {:.0}", per_minute),
+        ]
+    }
+}
+
+/// Garbage rows of the dig modes.
+pub struct Garbage {
+    pub rows: usize,
+    pub holes: Holes,
+}
+
+impl Garbage {
+    fn name(&self, mode: &str) -> String {
+        match self.holes {
+            Holes::Messy => format!("{} {} messy", mode, self.rows),
+            _ => format!("{} {}", mode, self.rows),
+        }
+    }
+}
+
+/// Race to clear the garbage the board starts with, ranked by time. Only
+/// boards cleared of all garbage are ranked.
+pub struct Dig(pub Garbage);
+
+impl GameMode for Dig {
+    fn name(&self) -> String {
+        self.0.name("dig")
+    }
+
+    fn completed(&self, game_state: &GameState) -> bool {
+        game_state.garbage_left() == 0
+    }
+
+    fn garbage(&self, game_state: &GameState) -> Option<(usize, Holes)> {
+        (game_state.pieces == 0).then_some((self.0.rows, self.0.holes))
+    }
+
+    fn ranked(&self, game_state: &GameState) -> bool {
+        game_state.game_over == Some(GameOverReason::Completed)
+    }
+
+    fn ranks_above(&self, entry: &Entry, other: &Entry) -> bool {
+        entry.duration < other.duration
+    }
+
+    fn ranked_by(&self) -> &'static str {
+        "time"
+    }
+
+    fn headline(&self, entry: &Entry) -> String {
+        precise_time(entry.duration)
+    }
+
+    fn panel(&self, game_state: &GameState) -> Vec<String> {
+        vec![
+            format!("garbage {}", game_state.garbage_left()),
+            format!("time    {}", precise_time(game_state.play_time())),
+        ]
+    }
+
+    fn clock(&self) -> bool {
+        true
+    }
+
+    fn verdict(&self, game_state: &GameState) -> &'static str {
+        if game_state.game_over == Some(GameOverReason::Completed) {
+            "CLEARED!"
+        } else {
+            "FINISHED!"
+        }
+    }
+
+    fn results(&self, game_state: &GameState) -> Vec<String> {
+        let dug = game_state.garbage_added - game_state.garbage_left();
+        vec![
+            format!("TIME  {}", precise_time(game_state.play_time())),
+            format!("GARBAGE  {}/{}", dug, game_state.garbage_added),
+            format!("PIECES  {}", game_state.pieces),
+        ]
+    }
+}
+
+/// Garbage is topped back up to the same height after every lock, lasts until
+/// topping out. Ranked by lines cleared.
+pub struct Survival(pub Garbage);
+
+impl GameMode for Survival {
+    fn name(&self) -> String {
+        self.0.name("survival")
+    }
+
+    fn garbage(&self, game_state: &GameState) -> Option<(usize, Holes)> {
+        let left = game_state.garbage_left() as usize;
+        (left < self.0.rows).then_some((self.0.rows - left, self.0.holes))
+    }
+
+    fn ranked(&self, _game_state: &GameState) -> bool {
+        true
+    }
+
+    fn ranks_above(&self, entry: &Entry, other: &Entry) -> bool {
+        entry.lines > other.lines
+    }
+
+    fn ranked_by(&self) -> &'static str {
+        "lines"
+    }
+
+    fn headline(&self, entry: &Entry) -> String {
+        entry.lines.to_string()
+    }
+
+    fn panel(&self, game_state: &GameState) -> Vec<String> {
+        let dug = game_state.garbage_added - game_state.garbage_left();
+        vec![format!("dug     {}", dug)]
+    }
+
+    fn results(&self, game_state: &GameState) -> Vec<String> {
+        let dug = game_state.garbage_added - game_state.garbage_left();
+        vec![
+            format!("LINES  {}", game_state.lines),
+            format!("DUG  {}", dug),
+            format!("TIME  {}", precise_time(game_state.play_time())),
+        ]
+    }
+}
+
+/// One of the two boards of a versus game, which lasts until either of them
+/// tops out. Nothing goes on the high scores.
+pub struct Versus;
+
+impl GameMode for Versus {
+    fn name(&self) -> String {
+        "versus".to_string()
+    }
+
+    fn ranked(&self, _game_state: &GameState) -> bool {
+        false
+    }
+
+    fn ranks_above(&self, entry: &Entry, other: &Entry) -> bool {
+        entry.score > other.score
+    }
+
+    fn ranked_by(&self) -> &'static str {
+        "score"
+    }
+
+    fn headline(&self, entry: &Entry) -> String {
+        entry.score.to_string()
+    }
+
+    fn panel(&self, game_state: &GameState) -> Vec<String> {
+        vec![
+            format!("sent    {}", game_state.garbage_sent),
+            format!("coming  {}", game_state.garbage_queue.pending()),
+        ]
+    }
+
+    fn results(&self, game_state: &GameState) -> Vec<String> {
+        vec![
+            format!("LINES  {}", game_state.lines),
+            format!("SENT  {}", game_state.garbage_sent),
+            format!("PIECES  {}", game_state.pieces),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(mode: Mode) -> GameState {
+        let rules = Rules {
+            mode,
+            ..Rules::default()
+        };
+        GameState::new(1, rules)
+    }
+
+    #[test]
+    fn precise_time_rounds_to_hundredths() {
+        assert_eq!(precise_time(Duration::ZERO), "0:00.00");
+        assert_eq!(precise_time(Duration::from_micros(59_994_999)), "0:59.99");
+        assert_eq!(precise_time(Duration::from_micros(59_995_000)), "1:00.00");
+        assert_eq!(precise_time(Duration::from_secs(60)), "1:00.00");
+        assert_eq!(precise_time(Duration::from_millis(61_500)), "1:01.50");
+        assert_eq!(precise_time(Duration::from_secs(3600)), "60:00.00");
+    }
+
+    #[test]
+    fn topped_out_sprint_isnt_ranked() {
+        let mut game_state = game(Mode::Sprint);
+        game_state.game_over = Some(GameOverReason::TopOut);
+        assert!(!Sprint.ranked(&game_state));
+        assert_eq!(Sprint.verdict(&game_state), "FINISHED!");
+
+        game_state.game_over = Some(GameOverReason::Completed);
+        assert!(Sprint.ranked(&game_state));
+        assert_eq!(Sprint.verdict(&game_state), "CLEARED!");
+    }
+
+    #[test]
+    fn sprint_pieces_and_keys() {
+        let mut game_state = game(Mode::Sprint);
+        let results = Sprint.results(&game_state);
+        assert_eq!(results[2..], ["PPS  0.00", "KPP  0.00"]);
+
+        game_state.started = Some(Duration::from_secs(1));
+        game_state.time_elapsed = Duration::from_secs(21);
+        game_state.pieces = 30;
+        game_state.key_presses = 75;
+        let results = Sprint.results(&game_state);
+        assert_eq!(
+            results[..4],
+            ["TIME  0:20.00", "PIECES  30", "PPS  1.50", "KPP  2.50"]
+        );
+    }
+
+    #[test]
+    fn sprint_shows_the_splits() {
+        let mut game_state = game(Mode::Sprint);
+        assert_eq!(Sprint.panel(&game_state).len(), 2);
+
+        game_state.splits = vec![Duration::from_secs(10), Duration::from_millis(21_250)];
+        let panel = Sprint.panel(&game_state);
+        assert_eq!(panel.last().unwrap(), "split   0:21.25");
+        let results = Sprint.results(&game_state);
+        assert_eq!(results[4..], ["10  0:10.00", "20  0:21.25"]);
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::VecDeque;
+
+use anyhow::Context;
+
+use super::controls::Action;
+use super::game_state::GameState;
+use super::protocol::{Garbage, Message};
+use super::replay::Input;
+use super::rules::Rules;
+
+/// Room joined when none is given
+pub const DEFAULT_ROOM: &str = "tetrs";
+
+/// WebSocket to the relay. Nothing blocks once it's open, messages that came
+/// in are picked up with `poll`.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Connection {
+    socket: tungstenite::WebSocket<std::net::TcpStream>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Connection {
+    /// Connects to a relay at a `ws://host:port` url.
+    pub fn open(url: &str) -> anyhow::Result<Connection> {
+        use tungstenite::client::IntoClientRequest;
+
+        let request = url
+            .into_client_request()
+            .with_context(|| format!("{} isn't a WebSocket url", url))?;
+        let host = request.uri().host().context("The url has no host")?;
+        let port = request.uri().port_u16().unwrap_or(80);
+        let stream = std::net::TcpStream::connect((host, port))
+            .with_context(|| format!("Couldn't connect to {}", url))?;
+        stream
+            .set_nodelay(true)
+            .context("Couldn't turn off Nagle's algorithm")?;
+        let (socket, _) = tungstenite::client(request, stream)
+            .map_err(|err| anyhow::anyhow!("{}", err))
+            .with_context(|| format!("Couldn't open a WebSocket to {}", url))?;
+        socket
+            .get_ref()
+            .set_nonblocking(true)
+            .context("Couldn't make the connection non-blocking")?;
+        log::info!("Connected to {}", url);
+        Ok(Connection { socket })
+    }
+
+    pub fn send(&mut self, message: &Message) -> anyhow::Result<()> {
+        let sent = self
+            .socket
+            .write_message(tungstenite::Message::Text(message.to_json()?));
+        match sent {
+            // Goes out with the next message or poll
+            Err(tungstenite::Error::Io(err)) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                Ok(())
+            }
+            sent => sent.context("Couldn't send to the relay"),
+        }
+    }
+
+    /// Messages from the relay since the last poll.
+    pub fn poll(&mut self) -> anyhow::Result<Vec<Message>> {
+        let mut messages = Vec::new();
+        loop {
+            match self.socket.read_message() {
+                Ok(tungstenite::Message::Text(text)) => messages.push(text.parse()?),
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(err))
+                    if err.kind() == std::io::ErrorKind::WouldBlock =>
+                {
+                    break
+                }
+                Err(err) => return Err(err).context("Lost the connection to the relay"),
+            }
+        }
+        match self.socket.write_pending() {
+            Err(tungstenite::Error::Io(err)) if err.kind() == std::io::ErrorKind::WouldBlock => {}
+            sent => sent.context("Couldn't send to the relay")?,
+        }
+        Ok(messages)
+    }
+}
+
+/// WebSocket of the browser to the relay, messages are queued by its
+/// callback until they are polled.
+#[cfg(target_arch = "wasm32")]
+pub struct Connection {
+    socket: web_sys::WebSocket,
+    inbox: std::rc::Rc<std::cell::RefCell<VecDeque<String>>>,
+    /// Messages sent before the socket was open
+    outbox: Vec<String>,
+    _on_message: wasm_bindgen::closure::Closure<dyn FnMut(web_sys::MessageEvent)>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Connection {
+    pub fn open(url: &str) -> anyhow::Result<Connection> {
+        use wasm_bindgen::JsCast;
+
+        let socket = web_sys::WebSocket::new(url)
+            .map_err(|err| anyhow::anyhow!("{:?}", err))
+            .with_context(|| format!("Couldn't open a WebSocket to {}", url))?;
+        let inbox = std::rc::Rc::new(std::cell::RefCell::new(VecDeque::new()));
+        let queue = inbox.clone();
+        let on_message =
+            wasm_bindgen::closure::Closure::wrap(Box::new(move |event: web_sys::MessageEvent| {
+                if let Some(text) = event.data().as_string() {
+                    queue.borrow_mut().push_back(text);
+                }
+            })
+                as Box<dyn FnMut(web_sys::MessageEvent)>);
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        Ok(Connection {
+            socket,
+            inbox,
+            outbox: Vec::new(),
+            _on_message: on_message,
+        })
+    }
+
+    pub fn send(&mut self, message: &Message) -> anyhow::Result<()> {
+        self.outbox.push(message.to_json()?);
+        self.flush()
+    }
+
+    pub fn poll(&mut self) -> anyhow::Result<Vec<Message>> {
+        if self.socket.ready_state() >= web_sys::WebSocket::CLOSING {
+            anyhow::bail!("Lost the connection to the relay");
+        }
+        self.flush()?;
+        let texts: Vec<_> = self.inbox.borrow_mut().drain(..).collect();
+        texts.iter().map(|text| text.parse()).collect()
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        if self.socket.ready_state() != web_sys::WebSocket::OPEN {
+            return Ok(());
+        }
+        for text in self.outbox.drain(..) {
+            self.socket
+                .send_with_str(&text)
+                .map_err(|err| anyhow::anyhow!("{:?}", err))
+                .context("Couldn't send to the relay")?;
+        }
+        Ok(())
+    }
+}
+
+/// Opponent's board, run from their inputs as they come in. Same seed, rules,
+/// inputs and garbage at the same ticks make the same game as on their side.
+pub struct Mirror {
+    pub board: GameState,
+    inputs: VecDeque<Input>,
+    garbage: VecDeque<Garbage>,
+}
+
+impl Mirror {
+    pub fn new(seed: u64, rules: Rules) -> Mirror {
+        Mirror {
+            board: GameState::new(seed, rules),
+            inputs: VecDeque::new(),
+            garbage: VecDeque::new(),
+        }
+    }
+
+    /// Runs the board up to the `tick` of a frame, with what happened in it.
+    /// Inputs of the tick itself go in too, they came before it runs.
+    pub fn frame(&mut self, tick: u64, inputs: Vec<Input>, garbage: Vec<Garbage>) {
+        self.inputs.extend(inputs);
+        self.garbage.extend(garbage);
+        while !self.board.is_finished() {
+            let now = self.board.steps_elapsed as u64;
+            while let Some(Input(_, action, pressed)) =
+                self.inputs.front().filter(|input| input.0 <= now).copied()
+            {
+                self.inputs.pop_front();
+                if pressed {
+                    self.board.press(action);
+                } else {
+                    self.board.release(action);
+                }
+            }
+            if now >= tick || self.board.is_finished() {
+                break;
+            }
+            while let Some(Garbage(_, rows)) = self
+                .garbage
+                .front()
+                .filter(|garbage| garbage.0 <= now)
+                .copied()
+            {
+                self.garbage.pop_front();
+                self.board.receive(rows);
+            }
+            self.board.step_time(super::TICK);
+        }
+    }
+}
+
+/// Versus game against someone else over the relay. The board played here is
+/// the usual one, the opponent's is a mirror.
+pub struct Online {
+    connection: Connection,
+    pub room: String,
+    pub mirror: Option<Mirror>,
+    /// Inputs since the last frame sent
+    inputs: Vec<Input>,
+    /// Garbage taken in since the last frame sent
+    garbage: Vec<Garbage>,
+    /// Attacks that came in, taken in at the next tick
+    incoming: Vec<u32>,
+    /// How the last game ended, from this side
+    pub result: Option<&'static str>,
+}
+
+impl Online {
+    /// Connects to the relay at `url` and waits in `room`.
+    pub fn connect(url: &str, room: &str, rules: &Rules) -> anyhow::Result<Online> {
+        let mut online = Online {
+            connection: Connection::open(url)?,
+            room: room.to_string(),
+            mirror: None,
+            inputs: Vec::new(),
+            garbage: Vec::new(),
+            incoming: Vec::new(),
+            result: None,
+        };
+        online.join(rules)?;
+        Ok(online)
+    }
+
+    /// Waits in the room for a game, again after one ended.
+    pub fn join(&mut self, rules: &Rules) -> anyhow::Result<()> {
+        self.mirror = None;
+        self.forget();
+        self.connection.send(&Message::Join {
+            room: self.room.clone(),
+            rules: rules.clone(),
+        })
+    }
+
+    /// Starts a game against the board the opponent plays from `seed`.
+    pub fn start(&mut self, seed: u64, rules: Rules) {
+        self.mirror = Some(Mirror::new(seed, rules));
+        self.forget();
+    }
+
+    /// Drops what was kept from the game before.
+    fn forget(&mut self) {
+        self.result = None;
+        self.inputs.clear();
+        self.garbage.clear();
+        self.incoming.clear();
+    }
+
+    pub fn poll(&mut self) -> anyhow::Result<Vec<Message>> {
+        self.connection.poll()
+    }
+
+    pub fn send(&mut self, message: &Message) -> anyhow::Result<()> {
+        self.connection.send(message)
+    }
+
+    /// Notes an input of the local board for the next frame.
+    pub fn record(&mut self, tick: u128, action: Action, pressed: bool) {
+        self.inputs.push(Input(tick as u64, action, pressed));
+    }
+
+    /// Keeps an attack from the opponent for the next tick.
+    pub fn attacked(&mut self, rows: u32) {
+        self.incoming.push(rows);
+    }
+
+    /// Gives the local board the garbage that came in, right before its tick
+    /// runs, so the mirror on the other side takes it at the same point.
+    pub fn take_in(&mut self, board: &mut GameState) {
+        let tick = board.steps_elapsed as u64;
+        for rows in self.incoming.drain(..) {
+            board.receive(rows);
+            self.garbage.push(Garbage(tick, rows));
+        }
+    }
+
+    /// Sends what happened to the local board since the last frame.
+    pub fn send_frame(&mut self, board: &GameState) -> anyhow::Result<()> {
+        let frame = Message::Frame {
+            tick: board.steps_elapsed as u64,
+            inputs: std::mem::take(&mut self.inputs),
+            garbage: std::mem::take(&mut self.garbage),
+        };
+        self.connection.send(&frame)
+    }
+}
@@ -0,0 +1,249 @@
+#[cfg(target_arch = "wasm32")]
+use instant::{Duration, Instant};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
+
+use std::collections::HashMap;
+
+use winit::dpi::PhysicalPosition;
+use winit::event::{ElementState, TouchPhase};
+
+use super::controls::Action;
+
+/// Pointer id the mouse goes by, touches get theirs from winit
+pub const MOUSE: u64 = u64::MAX;
+
+/// A tap has to be let go of sooner than this
+const TAP_TIME: Duration = Duration::from_millis(300);
+/// A swipe down quicker than this hard drops, a slower drag soft drops
+const SWIPE_TIME: Duration = Duration::from_millis(250);
+/// Cells a swipe down has to cover to hard drop
+const SWIPE_CELLS: f64 = 3.0;
+
+type Position = PhysicalPosition<f64>;
+
+/// A press and a release of an `action`, for gestures that don't last.
+fn tap(action: Action) -> Vec<(Action, ElementState)> {
+    vec![
+        (action, ElementState::Pressed),
+        (action, ElementState::Released),
+    ]
+}
+
+/// Finger or mouse going over the screen, from touching down to lifting.
+struct Gesture {
+    id: u64,
+    start: Position,
+    started: Instant,
+    /// Where the next shift is measured from, moves a cell with each shift
+    anchor_x: f64,
+    shifted: bool,
+    soft_dropping: bool,
+}
+
+impl Gesture {
+    fn new(id: u64, start: Position, now: Instant) -> Gesture {
+        Gesture {
+            id,
+            start,
+            started: now,
+            anchor_x: start.x,
+            shifted: false,
+            soft_dropping: false,
+        }
+    }
+
+    /// Shifts for every `cell` moved sideways, slowly dragging down holds the
+    /// soft drop.
+    fn moved(
+        &mut self,
+        position: Position,
+        cell: f64,
+        now: Instant,
+    ) -> Vec<(Action, ElementState)> {
+        let mut actions = Vec::new();
+        while position.x - self.anchor_x >= cell {
+            self.anchor_x += cell;
+            self.shifted = true;
+            actions.extend(tap(Action::MoveRight));
+        }
+        while self.anchor_x - position.x >= cell {
+            self.anchor_x -= cell;
+            self.shifted = true;
+            actions.extend(tap(Action::MoveLeft));
+        }
+
+        let down = position.y - self.start.y;
+        if !self.soft_dropping && down >= cell && now - self.started >= SWIPE_TIME {
+            self.soft_dropping = true;
+            actions.push((Action::SoftDrop, ElementState::Pressed));
+        }
+        actions
+    }
+
+    /// A quick swipe down hard drops and a short touch that stayed in place
+    /// rotates.
+    fn ended(self, position: Position, cell: f64, now: Instant) -> Vec<(Action, ElementState)> {
+        if self.soft_dropping {
+            return vec![(Action::SoftDrop, ElementState::Released)];
+        }
+
+        let down = position.y - self.start.y;
+        let across = (position.x - self.start.x).abs();
+        let elapsed = now - self.started;
+        if elapsed < SWIPE_TIME && down >= SWIPE_CELLS * cell && down > across {
+            tap(Action::HardDrop)
+        } else if elapsed < TAP_TIME && !self.shifted && down.abs() < cell && across < cell {
+            tap(Action::RotateClockwise)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Lets go of anything held without finishing the gesture.
+    fn cancelled(self) -> Vec<(Action, ElementState)> {
+        if self.soft_dropping {
+            vec![(Action::SoftDrop, ElementState::Released)]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Touches and mouse drags, turned into the same actions as the keys. One
+/// pointer at a time does gestures, any number can hold on-screen buttons.
+#[derive(Default)]
+pub struct Pointers {
+    gesture: Option<Gesture>,
+    /// On-screen buttons held down, by pointer
+    buttons: HashMap<u64, Action>,
+}
+
+impl Pointers {
+    /// Follows pointer `id` to a new `phase`. A pointer coming down on an
+    /// on-screen `button` holds it instead of starting a gesture. `cell` is
+    /// the size of a block on the screen, and `now` when the pointer got there.
+    pub fn update(
+        &mut self,
+        id: u64,
+        phase: TouchPhase,
+        position: Position,
+        button: Option<Action>,
+        cell: f64,
+        now: Instant,
+    ) -> Vec<(Action, ElementState)> {
+        match phase {
+            TouchPhase::Started => {
+                if let Some(action) = button {
+                    self.buttons.insert(id, action);
+                    vec![(action, ElementState::Pressed)]
+                } else {
+                    if self.gesture.is_none() {
+                        self.gesture = Some(Gesture::new(id, position, now));
+                    }
+                    Vec::new()
+                }
+            }
+            TouchPhase::Moved => match &mut self.gesture {
+                Some(gesture) if gesture.id == id => gesture.moved(position, cell, now),
+                _ => Vec::new(),
+            },
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                if let Some(action) = self.buttons.remove(&id) {
+                    return vec![(action, ElementState::Released)];
+                }
+                match self.gesture.take() {
+                    Some(gesture) if gesture.id == id => {
+                        if phase == TouchPhase::Ended {
+                            gesture.ended(position, cell, now)
+                        } else {
+                            gesture.cancelled()
+                        }
+                    }
+                    other => {
+                        self.gesture = other;
+                        Vec::new()
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CELL: f64 = 30.0;
+
+    fn at(x: f64, y: f64) -> Position {
+        PhysicalPosition::new(x, y)
+    }
+
+    fn after(start: Instant, millis: u64) -> Instant {
+        start + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn tap_rotates() {
+        let mut pointers = Pointers::default();
+        let start = Instant::now();
+        let actions = pointers.update(1, TouchPhase::Started, at(100.0, 100.0), None, CELL, start);
+        assert!(actions.is_empty());
+        let ended = at(105.0, 102.0);
+        let actions = pointers.update(1, TouchPhase::Ended, ended, None, CELL, after(start, 100));
+        assert_eq!(actions, tap(Action::RotateClockwise));
+    }
+
+    #[test]
+    fn swipe_sideways_shifts_once_a_cell() {
+        let mut pointers = Pointers::default();
+        let start = Instant::now();
+        pointers.update(1, TouchPhase::Started, at(100.0, 100.0), None, CELL, start);
+        let moved = at(100.0 + 2.5 * CELL, 100.0);
+        let actions = pointers.update(1, TouchPhase::Moved, moved, None, CELL, after(start, 50));
+        assert_eq!(
+            actions,
+            [tap(Action::MoveRight), tap(Action::MoveRight)].concat()
+        );
+        // Back past where it started
+        let moved = at(100.0 - 0.5 * CELL, 100.0);
+        let actions = pointers.update(1, TouchPhase::Moved, moved, None, CELL, after(start, 100));
+        assert_eq!(
+            actions,
+            [tap(Action::MoveLeft), tap(Action::MoveLeft)].concat()
+        );
+        // A shift isn't a tap
+        let actions = pointers.update(1, TouchPhase::Ended, moved, None, CELL, after(start, 150));
+        assert!(actions.is_empty());
+    }
+
+    #[test]
+    fn fast_swipe_down_hard_drops() {
+        let mut pointers = Pointers::default();
+        let start = Instant::now();
+        pointers.update(1, TouchPhase::Started, at(100.0, 100.0), None, CELL, start);
+        let moved = at(100.0, 100.0 + 2.0 * CELL);
+        let actions = pointers.update(1, TouchPhase::Moved, moved, None, CELL, after(start, 50));
+        assert!(actions.is_empty());
+        let ended = at(100.0, 100.0 + 4.0 * CELL);
+        let actions = pointers.update(1, TouchPhase::Ended, ended, None, CELL, after(start, 100));
+        assert_eq!(actions, tap(Action::HardDrop));
+    }
+
+    #[test]
+    fn slow_drag_down_soft_drops_until_let_go() {
+        let mut pointers = Pointers::default();
+        let start = Instant::now();
+        pointers.update(1, TouchPhase::Started, at(100.0, 100.0), None, CELL, start);
+        let moved = at(100.0, 100.0 + CELL);
+        let actions = pointers.update(1, TouchPhase::Moved, moved, None, CELL, after(start, 300));
+        assert_eq!(actions, vec![(Action::SoftDrop, ElementState::Pressed)]);
+        // Further down doesn't press it again
+        let moved = at(100.0, 100.0 + 5.0 * CELL);
+        let actions = pointers.update(1, TouchPhase::Moved, moved, None, CELL, after(start, 400));
+        assert!(actions.is_empty());
+        let actions = pointers.update(1, TouchPhase::Ended, moved, None, CELL, after(start, 500));
+        assert_eq!(actions, vec![(Action::SoftDrop, ElementState::Released)]);
+    }
+}
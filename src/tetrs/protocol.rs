@@ -0,0 +1,62 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use super::replay::Input;
+use super::rules::Rules;
+
+/// Rows of garbage taken in before the tick, after the inputs of the same
+/// tick. Stored as `[tick, rows]` like the inputs.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Garbage(pub u64, pub u32);
+
+/// What the clients and the relay say to each other, one message to a
+/// WebSocket text frame as JSON.
+///
+/// A client joins a room and waits there until a second one joins, then the
+/// relay picks a seed for each board and starts both. From then on each client
+/// plays its own board and sends its inputs, which the other one plays on a
+/// copy of it. The engine is deterministic, so the copy stays the same as the
+/// original. Attacks and the end of the game are told outright.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    /// Client wants to play in a room, the first one in sets the rules
+    Join { room: String, rules: Rules },
+    /// Relay has the client in the room, waiting for someone to join
+    Waiting,
+    /// Relay has two players in the room, the game starts
+    Start {
+        seed: u64,
+        opponent_seed: u64,
+        rules: Rules,
+    },
+    /// Client's board ran up to `tick` with these inputs and garbage since
+    /// the last frame
+    Frame {
+        tick: u64,
+        inputs: Vec<Input>,
+        garbage: Vec<Garbage>,
+    },
+    /// Client sends garbage to the other board
+    Attack { rows: u32 },
+    /// Client's board topped out
+    GameOver,
+    /// Relay lost the other client
+    Left,
+    /// Relay didn't take the client in
+    Refused { reason: String },
+}
+
+impl Message {
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        serde_json::to_string(self).context("Couldn't write the message")
+    }
+}
+
+impl std::str::FromStr for Message {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s).with_context(|| format!("Couldn't read the message {}", s))
+    }
+}
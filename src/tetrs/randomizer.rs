@@ -0,0 +1,213 @@
+use serde::{Deserialize, Serialize};
+
+use super::tetromino::{BlockState, NEXT_TETRO_BAG};
+
+/// Source of the tetrominos that are brought into the game.
+pub trait PieceGenerator {
+    fn next(&mut self) -> BlockState;
+}
+
+/// Available ways of generating the tetrominos.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Randomizer {
+    /// Every tetromino is picked independently
+    Uniform,
+    /// Guideline 7-bag, all seven tetrominos come before any of them repeats
+    Bag,
+    /// NES style, a tetromino same as the previous one is rerolled once
+    Classic,
+}
+
+impl std::default::Default for Randomizer {
+    fn default() -> Self {
+        Randomizer::Bag
+    }
+}
+
+impl std::str::FromStr for Randomizer {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(Randomizer::Uniform),
+            "bag" => Ok(Randomizer::Bag),
+            "classic" => Ok(Randomizer::Classic),
+            _ => Err(anyhow::Error::msg(format!("Unknown randomizer {}", s))),
+        }
+    }
+}
+
+impl Randomizer {
+    pub fn generator(&self, seed: u64) -> Box<dyn PieceGenerator> {
+        match self {
+            Randomizer::Uniform => Box::new(Uniform::new(seed)),
+            Randomizer::Bag => Box::new(Bag::new(seed)),
+            Randomizer::Classic => Box::new(Classic::new(seed)),
+        }
+    }
+}
+
+/// A small pseudo random number generator (SplitMix64). Same seed always gives
+/// the same sequence on every platform, which is what replays and races rely on.
+#[derive(Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+}
+
+/// Picks every tetromino independently, so the same one can come any number of
+/// times in a row.
+pub struct Uniform {
+    rng: Rng,
+}
+
+impl Uniform {
+    pub fn new(seed: u64) -> Uniform {
+        Uniform {
+            rng: Rng::new(seed),
+        }
+    }
+}
+
+impl PieceGenerator for Uniform {
+    fn next(&mut self) -> BlockState {
+        NEXT_TETRO_BAG[self.rng.below(NEXT_TETRO_BAG.len())]
+    }
+}
+
+/// Shuffles all seven tetrominos into a bag and deals them out one by one,
+/// refilling the bag when it's empty. There can be at most 12 tetrominos
+/// between two of the same kind.
+pub struct Bag {
+    rng: Rng,
+    bag: Vec<BlockState>,
+}
+
+impl Bag {
+    pub fn new(seed: u64) -> Bag {
+        Bag {
+            rng: Rng::new(seed),
+            bag: Vec::with_capacity(NEXT_TETRO_BAG.len()),
+        }
+    }
+
+    fn refill(&mut self) {
+        self.bag.extend_from_slice(&NEXT_TETRO_BAG);
+        // Fisher-Yates
+        for i in (1..self.bag.len()).rev() {
+            let j = self.rng.below(i + 1);
+            self.bag.swap(i, j);
+        }
+    }
+}
+
+impl PieceGenerator for Bag {
+    fn next(&mut self) -> BlockState {
+        if self.bag.is_empty() {
+            self.refill();
+        }
+        self.bag.pop().unwrap_or(BlockState::Eye)
+    }
+}
+
+/// Randomizer of the NES version. Rolls one of eight, if it's the extra eighth
+/// or the same as the previous tetromino, rolls once more from the seven and
+/// takes whatever comes.
+pub struct Classic {
+    rng: Rng,
+    previous: BlockState,
+}
+
+impl Classic {
+    pub fn new(seed: u64) -> Classic {
+        Classic {
+            rng: Rng::new(seed),
+            previous: BlockState::Emp,
+        }
+    }
+}
+
+impl PieceGenerator for Classic {
+    fn next(&mut self) -> BlockState {
+        let roll = self.rng.below(NEXT_TETRO_BAG.len() + 1);
+        let next = match NEXT_TETRO_BAG.get(roll) {
+            Some(tetro) if *tetro != self.previous => *tetro,
+            _ => NEXT_TETRO_BAG[self.rng.below(NEXT_TETRO_BAG.len())],
+        };
+        self.previous = next;
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deal(generator: &mut dyn PieceGenerator, count: usize) -> Vec<BlockState> {
+        (0..count).map(|_| generator.next()).collect()
+    }
+
+    #[test]
+    fn same_seed_same_sequence() {
+        for randomizer in [Randomizer::Uniform, Randomizer::Bag, Randomizer::Classic] {
+            let first = deal(randomizer.generator(42).as_mut(), 100);
+            let second = deal(randomizer.generator(42).as_mut(), 100);
+            let other = deal(randomizer.generator(43).as_mut(), 100);
+            assert!(first == second);
+            assert!(first != other);
+        }
+    }
+
+    #[test]
+    fn bag_deals_every_kind_once() {
+        let mut bag = Bag::new(7);
+        for _ in 0..100 {
+            let mut dealt = deal(&mut bag, NEXT_TETRO_BAG.len());
+            dealt.sort_by_key(|kind| *kind as u8);
+            let mut kinds = NEXT_TETRO_BAG.to_vec();
+            kinds.sort_by_key(|kind| *kind as u8);
+            assert!(dealt == kinds);
+        }
+    }
+
+    #[test]
+    fn classic_rerolls_once() {
+        let mut classic = Classic::new(9);
+        // Rolls the same numbers to see how many each tetromino took
+        let mut rng = Rng::new(9);
+        let mut previous = BlockState::Emp;
+        let mut rerolls = 0;
+        for _ in 0..1000 {
+            let roll = rng.below(NEXT_TETRO_BAG.len() + 1);
+            let expected = match NEXT_TETRO_BAG.get(roll) {
+                Some(tetro) if *tetro != previous => *tetro,
+                _ => {
+                    rerolls += 1;
+                    NEXT_TETRO_BAG[rng.below(NEXT_TETRO_BAG.len())]
+                }
+            };
+            assert!(classic.next() == expected);
+            previous = expected;
+        }
+        assert!(rerolls > 0);
+    }
+}
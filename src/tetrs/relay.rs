@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Context;
+use tungstenite::WebSocket;
+
+use super::protocol::Message;
+use super::rules::Rules;
+
+/// How long a connection waits for its client before looking at its mail
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// What a connection gets from the other connections
+enum Mail {
+    /// Other player of the room, to pass the messages on to
+    Paired(Sender<Mail>),
+    Message(Message),
+}
+
+/// Player waiting in a room for a second one
+struct Waiting {
+    /// Number of the connection, to know it's the same one when it leaves
+    id: u64,
+    mailbox: Sender<Mail>,
+    rules: Rules,
+}
+
+type Rooms = Arc<Mutex<HashMap<String, Waiting>>>;
+
+/// Relays messages between the two players of each room, every connection on
+/// a thread of its own. Returns only if the listener fails.
+pub fn serve(listener: TcpListener) -> anyhow::Result<()> {
+    let rooms = Rooms::default();
+    for (id, stream) in listener.incoming().enumerate() {
+        let stream = stream.context("Couldn't accept a connection")?;
+        let rooms = rooms.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = Connection::accept(id as u64, stream, rooms).and_then(|c| c.run()) {
+                log::warn!("Connection {} failed: {:?}", id, err);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// A client of the relay and the player it's paired with.
+struct Connection {
+    id: u64,
+    socket: WebSocket<TcpStream>,
+    rooms: Rooms,
+    /// Room it joined, until it's paired
+    room: Option<String>,
+    peer: Option<Sender<Mail>>,
+    mailbox: Sender<Mail>,
+    mail: Receiver<Mail>,
+}
+
+impl Connection {
+    fn accept(id: u64, stream: TcpStream, rooms: Rooms) -> anyhow::Result<Connection> {
+        let socket = tungstenite::accept(stream).context("Couldn't open the WebSocket")?;
+        // Reads give up now and then to pass on the mail
+        socket
+            .get_ref()
+            .set_read_timeout(Some(POLL_INTERVAL))
+            .context("Couldn't set the read timeout")?;
+        let (mailbox, mail) = mpsc::channel();
+        log::info!("Connection {} opened", id);
+        Ok(Connection {
+            id,
+            socket,
+            rooms,
+            room: None,
+            peer: None,
+            mailbox,
+            mail,
+        })
+    }
+
+    fn run(mut self) -> anyhow::Result<()> {
+        let result = self.relay();
+        self.leave()?;
+        log::info!("Connection {} closed", self.id);
+        result
+    }
+
+    /// Passes messages on both ways until the client goes.
+    fn relay(&mut self) -> anyhow::Result<()> {
+        loop {
+            match self.socket.read_message() {
+                Ok(tungstenite::Message::Text(text)) => self.handle(text.parse()?)?,
+                Ok(tungstenite::Message::Close(_)) => return Ok(()),
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(err))
+                    if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                    return Ok(())
+                }
+                Err(err) => return Err(err).context("Couldn't read from the client"),
+            }
+
+            while let Ok(mail) = self.mail.try_recv() {
+                match mail {
+                    Mail::Paired(peer) => {
+                        self.room = None;
+                        self.peer = Some(peer);
+                    }
+                    Mail::Message(message) => self.send(&message)?,
+                }
+            }
+        }
+    }
+
+    fn handle(&mut self, message: Message) -> anyhow::Result<()> {
+        match message {
+            Message::Join { room, rules } => self.join(room, rules),
+            Message::Frame { .. } | Message::Attack { .. } | Message::GameOver => {
+                if let Some(peer) = &self.peer {
+                    // The other player leaving is told by its own connection
+                    let _ = peer.send(Mail::Message(message));
+                }
+                Ok(())
+            }
+            _ => self.send(&Message::Refused {
+                reason: "Only players send that".to_string(),
+            }),
+        }
+    }
+
+    /// Pairs the client with the one waiting in the `room`, or has it wait
+    /// there. Joining again leaves the game being played.
+    fn join(&mut self, room: String, rules: Rules) -> anyhow::Result<()> {
+        self.leave()?;
+        let mut rooms = self.rooms.lock().ok().context("Rooms are poisoned")?;
+        if let Some(waiting) = rooms.remove(&room) {
+            let seed: u64 = random_number::random!();
+            let opponent_seed: u64 = random_number::random!();
+            let start = Message::Start {
+                seed: opponent_seed,
+                opponent_seed: seed,
+                rules: waiting.rules.clone(),
+            };
+            let paired = waiting
+                .mailbox
+                .send(Mail::Paired(self.mailbox.clone()))
+                .and_then(|_| waiting.mailbox.send(Mail::Message(start)));
+            if paired.is_ok() {
+                drop(rooms);
+                log::info!(
+                    "Connections {} and {} play in {}",
+                    waiting.id,
+                    self.id,
+                    room
+                );
+                self.peer = Some(waiting.mailbox);
+                return self.send(&Message::Start {
+                    seed,
+                    opponent_seed,
+                    rules: waiting.rules,
+                });
+            }
+        }
+        rooms.insert(
+            room.clone(),
+            Waiting {
+                id: self.id,
+                mailbox: self.mailbox.clone(),
+                rules,
+            },
+        );
+        drop(rooms);
+        self.room = Some(room);
+        self.send(&Message::Waiting)
+    }
+
+    /// Gets out of the room the client waits in, or tells the other player
+    /// it left.
+    fn leave(&mut self) -> anyhow::Result<()> {
+        if let Some(room) = self.room.take() {
+            let mut rooms = self.rooms.lock().ok().context("Rooms are poisoned")?;
+            if rooms
+                .get(&room)
+                .is_some_and(|waiting| waiting.id == self.id)
+            {
+                rooms.remove(&room);
+            }
+        }
+        // Whoever took it out of the room paired up under the lock, so the mail
+        // is here by now even if it wasn't read yet
+        while let Ok(mail) = self.mail.try_recv() {
+            if let Mail::Paired(peer) = mail {
+                self.peer = Some(peer);
+            }
+        }
+        if let Some(peer) = self.peer.take() {
+            let _ = peer.send(Mail::Message(Message::Left));
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, message: &Message) -> anyhow::Result<()> {
+        self.socket
+            .write_message(tungstenite::Message::Text(message.to_json()?))
+            .context("Couldn't write to the client")
+    }
+}
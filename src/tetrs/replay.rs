@@ -0,0 +1,250 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use super::controls::Action;
+use super::rules::Rules;
+
+/// Playback speeds to pick from, as multiples of real time
+pub const SPEEDS: [f64; 5] = [0.5, 1.0, 1.5, 2.0, 4.0];
+/// Speed a replay starts at
+const NORMAL_SPEED: usize = 1;
+
+/// Where the last replay is kept in the browser's local storage
+#[cfg(target_arch = "wasm32")]
+const STORAGE_KEY: &str = "tetrs.replay";
+
+/// An action pressed or released, with the tick of the simulation it happened
+/// before. Stored as `[tick, action, pressed]` to keep replays short.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Input(pub u64, pub Action, pub bool);
+
+/// Everything needed to play a game again: the engine is deterministic, so
+/// the seed, the rules and the inputs at the same ticks give the same game.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub rules: Rules,
+    pub inputs: Vec<Input>,
+}
+
+impl Replay {
+    /// Empty recording of a game about to start.
+    pub fn new(seed: u64, rules: Rules) -> Replay {
+        Replay {
+            seed,
+            rules,
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Notes an `action` going down or up before the simulation's `tick`.
+    pub fn record(&mut self, tick: u128, action: Action, pressed: bool) {
+        self.inputs.push(Input(tick as u64, action, pressed));
+    }
+
+    /// Replay from a file written by `save`, or shared by someone else.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open(path: &std::path::Path) -> anyhow::Result<Replay> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("Couldn't load {}", path.display()))
+    }
+
+    /// Writes the replay to `replays` in the user's data directory, named
+    /// after its seed.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self) -> anyhow::Result<()> {
+        let dir = Replay::dir()?;
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Couldn't create {}", dir.display()))?;
+        let path = dir.join(format!("{}.json", self.seed));
+        let text = serde_json::to_string(self).context("Couldn't write the replay")?;
+        std::fs::write(&path, text)
+            .with_context(|| format!("Couldn't write {}", path.display()))?;
+        log::info!("Saved the replay to {}", path.display());
+        Ok(())
+    }
+
+    /// Most recently saved replay, if there is any.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_last() -> anyhow::Result<Option<Replay>> {
+        let dir = Replay::dir()?;
+        if !dir.exists() {
+            return Ok(None);
+        }
+        let entries =
+            std::fs::read_dir(&dir).with_context(|| format!("Couldn't read {}", dir.display()))?;
+        let last = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+            .max_by_key(|(modified, _)| *modified);
+        match last {
+            Some((_, path)) => Replay::open(&path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn dir() -> anyhow::Result<std::path::PathBuf> {
+        Ok(dirs::data_dir()
+            .context("Can't find the data directory")?
+            .join("tetrs")
+            .join("replays"))
+    }
+
+    /// Keeps the replay in the browser's local storage, replacing the one
+    /// before.
+    #[cfg(target_arch = "wasm32")]
+    pub fn save(&self) -> anyhow::Result<()> {
+        let text = serde_json::to_string(self).context("Couldn't write the replay")?;
+        local_storage()?
+            .set_item(STORAGE_KEY, &text)
+            .ok()
+            .context("Couldn't store the replay")
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load_last() -> anyhow::Result<Option<Replay>> {
+        match local_storage()?.get_item(STORAGE_KEY).ok().flatten() {
+            Some(text) => serde_json::from_str(&text)
+                .map(Some)
+                .context("Couldn't load the replay"),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> anyhow::Result<web_sys::Storage> {
+    web_sys::window()
+        .and_then(|win| win.local_storage().ok().flatten())
+        .context("Can't access the local storage")
+}
+
+/// A replay being watched, hands out its inputs as the ticks come.
+pub struct Playback {
+    replay: Replay,
+    /// Index of the first input not handed out yet
+    next: usize,
+    speed: usize,
+    pub paused: bool,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Playback {
+        Playback {
+            replay,
+            next: 0,
+            speed: NORMAL_SPEED,
+            paused: false,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.replay.seed
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.replay.rules.clone()
+    }
+
+    /// Multiple of real time the replay plays at.
+    pub fn speed(&self) -> f64 {
+        SPEEDS[self.speed]
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+
+    /// Inputs recorded before `tick`, in the order they happened.
+    pub fn due(&mut self, tick: u128) -> Vec<Input> {
+        let start = self.next;
+        while self.next < self.replay.inputs.len()
+            && self.replay.inputs[self.next].0 as u128 <= tick
+        {
+            self.next += 1;
+        }
+        self.replay.inputs[start..self.next].to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::game_state::GameState;
+    use super::super::TICK;
+    use super::*;
+
+    /// Ticks a game runs for in the tests
+    const TICKS: u64 = 1200;
+
+    /// Keys of a short game, each held for a few ticks.
+    fn script() -> Vec<Input> {
+        let actions = [
+            Action::MoveLeft,
+            Action::RotateClockwise,
+            Action::HardDrop,
+            Action::MoveRight,
+            Action::Hold,
+            Action::SoftDrop,
+            Action::Rotate180,
+            Action::HardDrop,
+            Action::RotateCounterClockwise,
+            Action::HardDrop,
+        ];
+        (0..TICKS / 12)
+            .flat_map(|i| {
+                let action = actions[i as usize % actions.len()];
+                [
+                    Input(i * 12, action, true),
+                    Input(i * 12 + 5, action, false),
+                ]
+            })
+            .collect()
+    }
+
+    /// Runs a game from `seed`, giving it the `inputs` due before each tick.
+    fn play(seed: u64, rules: Rules, mut due: impl FnMut(u128) -> Vec<Input>) -> GameState {
+        let mut game_state = GameState::new(seed, rules);
+        for _ in 0..TICKS {
+            for Input(_, action, pressed) in due(game_state.steps_elapsed) {
+                if pressed {
+                    game_state.press(action);
+                } else {
+                    game_state.release(action);
+                }
+            }
+            game_state.step_time(TICK);
+        }
+        game_state
+    }
+
+    #[test]
+    fn playback_plays_the_same_game() {
+        let script = script();
+        let mut recording = Replay::new(7, Rules::default());
+        let mut next = 0;
+        let played = play(7, Rules::default(), |tick| {
+            let start = next;
+            while next < script.len() && script[next].0 as u128 <= tick {
+                let Input(tick, action, pressed) = script[next];
+                recording.record(tick as u128, action, pressed);
+                next += 1;
+            }
+            script[start..next].to_vec()
+        });
+        assert!(played.pieces > 0);
+
+        let mut playback = Playback::new(recording);
+        let replayed = play(playback.seed(), playback.rules(), |tick| playback.due(tick));
+        assert_eq!(replayed.score, played.score);
+        assert_eq!(replayed.lines, played.lines);
+        assert_eq!(replayed.pieces, played.pieces);
+        assert!(replayed.blocks == played.blocks);
+    }
+}
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+
+use super::tetromino::BlockState;
+
+/// Which way a tetromino is facing, relative to how it spawns.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Orientation {
+    Spawn,
+    Right,
+    Reverse,
+    Left,
+}
+
+impl Orientation {
+    pub fn rotated(&self, rotation: Rotation) -> Orientation {
+        let turns = match rotation {
+            Rotation::Clockwise => 1,
+            Rotation::Half => 2,
+            Rotation::CounterClockwise => 3,
+        };
+        match (self.index() + turns) % 4 {
+            0 => Orientation::Spawn,
+            1 => Orientation::Right,
+            2 => Orientation::Reverse,
+            _ => Orientation::Left,
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            Orientation::Spawn => 0,
+            Orientation::Right => 1,
+            Orientation::Reverse => 2,
+            Orientation::Left => 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rotation {
+    Clockwise,
+    CounterClockwise,
+    Half,
+}
+
+/// Decides where a rotated tetromino may end up when it doesn't fit in place.
+pub trait RotationSystem {
+    /// Offsets to try in order, `x` to the right and `y` down, when rotating a
+    /// tetromino of `kind` that is facing `from`. First one that fits is taken.
+    fn kicks(&self, kind: BlockState, from: Orientation, rotation: Rotation) -> Vec<(i8, i8)>;
+}
+
+/// Available rotation systems.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RotationStyle {
+    /// Rotates in place, or doesn't rotate at all
+    Classic,
+    /// Guideline Super Rotation System with wall kicks
+    Srs,
+}
+
+impl std::default::Default for RotationStyle {
+    fn default() -> Self {
+        RotationStyle::Srs
+    }
+}
+
+impl std::str::FromStr for RotationStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "classic" => Ok(RotationStyle::Classic),
+            "srs" => Ok(RotationStyle::Srs),
+            _ => Err(anyhow::Error::msg(format!("Unknown rotation system {}", s))),
+        }
+    }
+}
+
+impl RotationStyle {
+    pub fn system(&self) -> Box<dyn RotationSystem> {
+        match self {
+            RotationStyle::Classic => Box::new(Classic),
+            RotationStyle::Srs => Box::new(Srs),
+        }
+    }
+}
+
+/// No kicks, a rotation that collides is rejected.
+pub struct Classic;
+
+impl RotationSystem for Classic {
+    fn kicks(&self, _kind: BlockState, _from: Orientation, _rotation: Rotation) -> Vec<(i8, i8)> {
+        vec![(0, 0)]
+    }
+}
+
+// Kick tables as they are usually written, `x` to the right and `y` up.
+// Rows are 0->R, R->0, R->2, 2->R, 2->L, L->2, L->0, 0->L.
+
+const JLSTZ_KICKS: [[(i8, i8); 5]; 8] = [
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+];
+
+const I_KICKS: [[(i8, i8); 5]; 8] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+];
+
+// There are no 180 kicks in the guideline, these are the commonly used ones.
+// Rows are 0->2, R->L, 2->0, L->R.
+const HALF_KICKS: [[(i8, i8); 6]; 4] = [
+    [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)],
+    [(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)],
+    [(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)],
+    [(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)],
+];
+
+/// Super Rotation System
+pub struct Srs;
+
+impl RotationSystem for Srs {
+    fn kicks(&self, kind: BlockState, from: Orientation, rotation: Rotation) -> Vec<(i8, i8)> {
+        let kicks: &[(i8, i8)] = match (kind, rotation) {
+            (BlockState::Ohh, _) => &[(0, 0)],
+            (_, Rotation::Half) => &HALF_KICKS[from.index()],
+            (BlockState::Eye, _) => &I_KICKS[Srs::row(from, rotation)],
+            _ => &JLSTZ_KICKS[Srs::row(from, rotation)],
+        };
+
+        // Tables are written with `y` up, board has it down
+        kicks.iter().map(|(x, y)| (*x, -*y)).collect()
+    }
+}
+
+impl Srs {
+    fn row(from: Orientation, rotation: Rotation) -> usize {
+        match rotation {
+            Rotation::Clockwise => from.index() * 2,
+            _ => (from.index() * 2 + 7) % 8,
+        }
+    }
+}
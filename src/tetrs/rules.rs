@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::controls::Handling;
+use super::mode::Mode;
+use super::randomizer::Randomizer;
+use super::rotation::RotationStyle;
+use super::scoring::ScoringStyle;
+
+pub const MIN_PREVIEW: usize = 1;
+pub const MAX_PREVIEW: usize = 6;
+
+/// Settings of the game engine that a game is started with. Two games with the
+/// same rules and the same seed play out the same.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Rules {
+    pub mode: Mode,
+    pub randomizer: Randomizer,
+    /// Number of upcoming tetrominos visible, between `MIN_PREVIEW` and `MAX_PREVIEW`
+    pub preview_count: usize,
+    pub rotation: RotationStyle,
+    /// How long a tetromino can rest on the stack before it locks
+    pub lock_delay: Duration,
+    /// How many times moving or rotating can restart the lock delay
+    pub max_lock_resets: u8,
+    pub scoring: ScoringStyle,
+    pub handling: Handling,
+    /// Rows of garbage the dig modes keep on the board
+    pub garbage_rows: u8,
+    /// Garbage rows can have a second hole
+    pub messy_garbage: bool,
+    /// How long garbage from the opponent waits before it can come in
+    pub garbage_delay: Duration,
+}
+
+impl std::default::Default for Rules {
+    fn default() -> Self {
+        Rules {
+            mode: Mode::default(),
+            randomizer: Randomizer::default(),
+            preview_count: 3,
+            rotation: RotationStyle::default(),
+            lock_delay: Duration::from_millis(500),
+            max_lock_resets: 15,
+            scoring: ScoringStyle::default(),
+            handling: Handling::default(),
+            garbage_rows: 10,
+            messy_garbage: false,
+            garbage_delay: Duration::from_millis(500),
+        }
+    }
+}
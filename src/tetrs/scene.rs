@@ -1,4 +1,4 @@
-use std::{borrow::Cow, cmp};
+use std::{borrow::Cow, cmp, collections::VecDeque};
 
 use anyhow::Context;
 use wgpu_text::section::{
@@ -7,13 +7,17 @@ use wgpu_text::section::{
 
 use super::base::Base;
 use super::colours;
-use super::drawable::Geometry;
+use super::controls::Action;
+use super::drawable::{Drawable, Geometry};
 use super::game_state;
+use super::mode::GameMode;
+use super::scores::{self, Entry};
 use super::tetromino::{BlockState, CurrentTetromino, Tetromino};
 use super::vertex::Vertex;
 use super::vertex::{ScreenCoord, ToVertices};
 use super::writer::Writer;
 
+/// Width of a single arena with its side panel
 pub const SCREEN_WIDTH: u32 = 30; // Blocks
 pub const SCREEN_HEIGHT: u32 = 30; // Blocks
 pub const GAME_AREA_WIDTH: u32 = game_state::NUM_COLS as u32; // Blocks
@@ -22,6 +26,24 @@ pub const LEFT_MARGIN: u32 = 1; // Blocks
 pub const TOP_MARGIN: u32 = 1; // Blocks
 pub const SPACE: u32 = 1; // Blocks
 pub const BOTTOM_MARGIN: u32 = 1; // Blocks
+pub const SECOND_COLUMN: u32 = GAME_AREA_WIDTH + 7; // Blocks
+pub const HOLD_BOX_SIZE: u32 = 5; // Blocks
+pub const BUTTON_SIZE: u32 = 3; // Blocks
+/// Where rank, name, score, level, lines, time and date go on the high scores
+pub const SCORE_COLUMNS: [u32; 7] = [0, 2, 8, 13, 16, 19, 23]; // Blocks
+
+/// On-screen buttons for touch screens with their labels and the blocks of
+/// their bottom left corners, below the next queue
+const BUTTONS: [(Action, &str, u32, u32); 8] = [
+    (Action::RotateCounterClockwise, "ccw", 14, 6),
+    (Action::RotateClockwise, "cw", 18, 6),
+    (Action::Rotate180, "180", 22, 6),
+    (Action::Hold, "hold", 26, 6),
+    (Action::MoveLeft, "<", 14, 2),
+    (Action::SoftDrop, "v", 18, 2),
+    (Action::MoveRight, ">", 22, 2),
+    (Action::HardDrop, "drop", 26, 2),
+];
 
 pub type Frame = winit::dpi::PhysicalSize<u32>;
 
@@ -31,6 +53,10 @@ pub struct Scene {
     line_weight: u32,
     scene_size: Frame,
     window_size: Frame,
+    /// Width of the scene, wide enough for all the arenas side by side
+    columns: u32, // Blocks
+    /// Left edge of the arena being drawn, text goes relative to it too
+    arena_left: u32, // Blocks
     pipeline: wgpu::RenderPipeline,
     writer: Writer,
 }
@@ -42,14 +68,16 @@ impl<'a> Scene {
             .context("Couldn't initialize base")?;
         let window_size = base.window_size.clone();
 
-        let block_size: u32 = Scene::calculate_block_size(&window_size);
+        let block_size: u32 = Scene::calculate_block_size(&window_size, SCREEN_WIDTH);
 
         let writer = Writer::new(&base).context("Couldn't create the text writer")?;
 
         Ok(Scene {
             pipeline: Scene::build_pipeline(&base),
             window_size,
-            scene_size: Frame::new(SCREEN_HEIGHT * block_size, SCREEN_WIDTH * block_size),
+            scene_size: Frame::new(SCREEN_WIDTH * block_size, SCREEN_HEIGHT * block_size),
+            columns: SCREEN_WIDTH,
+            arena_left: LEFT_MARGIN,
             block_size,
             line_weight: 12,
             writer,
@@ -70,12 +98,8 @@ impl<'a> Scene {
         self.base
             .surface
             .configure(&self.base.device, &self.base.surface_config);
-        self.block_size = Scene::calculate_block_size(new_size);
-        self.scene_size = Frame::new(
-            SCREEN_HEIGHT * self.block_size,
-            SCREEN_WIDTH * self.block_size,
-        );
         self.window_size = new_size.clone();
+        self.fit();
         self.writer.brush.resize_view(
             new_size.width as f32,
             new_size.height as f32,
@@ -83,82 +107,252 @@ impl<'a> Scene {
         );
     }
 
+    /// Lays the scene out for `count` arenas side by side, blocks get smaller
+    /// to fit them all in the window.
+    pub fn set_arenas(&mut self, count: u32) {
+        let columns = SCREEN_WIDTH * count;
+        if columns != self.columns {
+            self.columns = columns;
+            self.fit();
+        }
+    }
+
+    /// Sizes the blocks and the scene to the window.
+    fn fit(&mut self) {
+        self.block_size = Scene::calculate_block_size(&self.window_size, self.columns);
+        self.scene_size = Frame::new(
+            self.columns * self.block_size,
+            SCREEN_HEIGHT * self.block_size,
+        );
+    }
+
     pub fn game_scene(&mut self, view: &wgpu::TextureView, game_state: &super::GameState) {
+        self.arena(view, game_state, wgpu::LoadOp::Clear(wgpu::Color::BLACK));
+    }
+
+    /// Both boards of a versus game, the first one on the left.
+    pub fn versus_scene(&mut self, view: &wgpu::TextureView, boards: [&super::GameState; 2]) {
+        for (idx, board) in boards.into_iter().enumerate() {
+            self.arena_left = LEFT_MARGIN + SCREEN_WIDTH * idx as u32;
+            let load = if idx == 0 {
+                wgpu::LoadOp::Clear(wgpu::Color::BLACK)
+            } else {
+                wgpu::LoadOp::Load
+            };
+            self.arena(view, board, load);
+        }
+        self.arena_left = LEFT_MARGIN;
+    }
+
+    /// A board with its side panel at the current arena.
+    fn arena(
+        &mut self,
+        view: &wgpu::TextureView,
+        game_state: &super::GameState,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) {
         let game_arena = self.game_arena();
         let committed_blocks = self.blocks(game_state);
-        let next_tetromino = self.next_tetromino_geom(&game_state.next_tetromino.tetromino);
+        let next_tetrominos = self.next_tetrominos_geom(&game_state.next_tetrominos);
+        let held_tetromino = self.held_tetromino_geom(game_state);
+        let ghost_tetromino = self.ghost_tetromino_geom(&game_state.ghost_tetromino());
         let current_tetromino = self.current_tetromino_geom(&game_state.current_tetromino);
-
-        let all = (game_arena + committed_blocks + next_tetromino + current_tetromino)
+        let garbage_meter = self.garbage_meter_geom(game_state.garbage_queue.pending());
+
+        let all = (game_arena
+            + committed_blocks
+            + next_tetrominos
+            + held_tetromino
+            + ghost_tetromino
+            + current_tetromino
+            + garbage_meter)
             .to_drawable(&self.base);
-
-        let mut encoder =
-            self.base
-                .device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("Game scene command encoder"),
-                });
-
-        {
-            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Game render pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-
-            rpass.set_pipeline(&self.pipeline);
-
-            rpass.set_index_buffer(all.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            rpass.set_vertex_buffer(0, all.vertex_buffer.slice(..));
-            rpass.draw_indexed(0..all.index_buffer_len, 0, 0..1);
-        }
-        self.base.queue.submit([encoder.finish()]);
+        self.draw(view, &all, load);
 
         // Text
         self.write(&view, "next", SPACE * 1, GAME_AREA_WIDTH, false);
+        self.write(&view, "hold", SPACE * 1, SECOND_COLUMN, false);
         self.write(
             &view,
             format!("score   {}", game_state.score).as_str(),
-            SPACE * 12,
-            GAME_AREA_WIDTH,
+            SPACE * 8,
+            SECOND_COLUMN,
             false,
         );
         self.write(
             &view,
             format!("level   {}", game_state.level).as_str(),
-            SPACE * 14,
-            GAME_AREA_WIDTH,
+            SPACE * 10,
+            SECOND_COLUMN,
             false,
         );
+        self.write(
+            &view,
+            format!("lines   {}", game_state.lines).as_str(),
+            SPACE * 12,
+            SECOND_COLUMN,
+            false,
+        );
+        if let Some(clear) = game_state.last_clear {
+            self.write(
+                &view,
+                clear.name().as_str(),
+                SPACE * 14,
+                SECOND_COLUMN,
+                false,
+            );
+        }
+        if game_state.combo > 1 {
+            self.write(
+                &view,
+                format!("combo   {}", game_state.combo - 1).as_str(),
+                SPACE * 15,
+                SECOND_COLUMN,
+                false,
+            );
+        }
+        let panel = game_state.mode().panel(game_state);
+        self.write_lines(view, &panel, SPACE * 17, SECOND_COLUMN);
+    }
+
+    /// Outlined buttons over whatever is already drawn.
+    pub fn render_buttons(&mut self, view: &wgpu::TextureView) {
+        let border = self.line_weight / 4;
+        let mut blx = Geometry::default();
+        for (_, _, left, bottom) in BUTTONS {
+            let (b_left, b_top, b_right, b_bottom) = (
+                self.block_size * left,
+                self.block_size * (bottom + BUTTON_SIZE),
+                self.block_size * (left + BUTTON_SIZE),
+                self.block_size * bottom,
+            );
+            blx += self.rectangle(b_left, b_top, b_right, b_bottom, colours::DARK_GREEN);
+            blx += self.rectangle(
+                b_left + border,
+                b_top - border,
+                b_right - border,
+                b_bottom + border,
+                colours::BLACK,
+            );
+        }
+        let all = blx.to_drawable(&self.base);
+        self.draw(view, &all, wgpu::LoadOp::Load);
+
+        for (_, label, left, bottom) in BUTTONS {
+            // Text is placed past the margins and a space, single characters
+            // are moved towards the middle
+            let offset = if label.len() == 1 { 1 } else { 0 };
+            let x = left + offset - LEFT_MARGIN - SPACE;
+            let y = SCREEN_HEIGHT - bottom - BUTTON_SIZE + 1 - TOP_MARGIN - SPACE;
+            self.write(view, label, y, x, false);
+        }
+    }
+
+    /// On-screen button under a `position` in the window.
+    pub fn button_at(&self, position: winit::dpi::PhysicalPosition<f64>) -> Option<Action> {
+        // Window can be smaller than the scene for a moment while resizing
+        let left_margin = self.window_size.width.saturating_sub(self.scene_size.width) / 2;
+        let bottom_margin = self
+            .window_size
+            .height
+            .saturating_sub(self.scene_size.height)
+            / 2;
+        let bs = self.block_size as f64;
+        let x = (position.x - left_margin as f64) / bs;
+        let y = ((self.window_size.height - bottom_margin) as f64 - position.y) / bs;
+
+        BUTTONS
+            .iter()
+            .find(|(_, _, left, bottom)| {
+                let (left, bottom) = (*left as f64, *bottom as f64);
+                let size = BUTTON_SIZE as f64;
+                x >= left && x < left + size && y >= bottom && y < bottom + size
+            })
+            .map(|(action, _, _, _)| *action)
+    }
+
+    pub fn block_size(&self) -> u32 {
+        self.block_size
+    }
+
+    pub fn render_pause(&mut self, view: &wgpu::TextureView, hints: &[String]) {
+        self.render_banner(view, "PAUSED", hints);
+    }
+
+    /// A `title` with `hints` under it over the middle of the board.
+    pub fn render_banner(&mut self, view: &wgpu::TextureView, title: &str, hints: &[String]) {
+        self.write(view, title, SPACE * 12, SPACE * 3, false);
+        self.write_lines(view, hints, SPACE * 14, SPACE * 3);
     }
 
-    pub fn render_pause(&mut self, view: &wgpu::TextureView) {
-        self.write(&view, "PAUSED", SPACE * 12, SPACE * 3, false);
+    /// Playback status and controls under the stats while watching a replay.
+    pub fn render_replay(&mut self, view: &wgpu::TextureView, lines: &[String]) {
+        self.write(view, "REPLAY", SPACE * 20, SECOND_COLUMN, false);
+        self.write_lines(view, lines, SPACE * 22, SECOND_COLUMN);
+    }
+
+    pub fn title_scene(&mut self, view: &wgpu::TextureView, hints: &[String]) {
+        self.write(view, "TETRS", SPACE * 6, SPACE * 5, true);
+        self.write_lines(view, hints, SPACE * 9, SPACE * 5);
+    }
+
+    /// Best results of a `mode`, the `highlight`ed one is the one just entered.
+    pub fn scores_scene(
+        &mut self,
+        view: &wgpu::TextureView,
+        mode: &dyn GameMode,
+        entries: &[&Entry],
+        highlight: Option<usize>,
+        hints: &[String],
+    ) {
+        self.write(view, "HIGH SCORES", SPACE * 3, 0, true);
+        self.write(view, &mode.name(), SPACE * 3, SCORE_COLUMNS[3], false);
+
+        let header = ["", "name", mode.ranked_by(), "lv", "lines", "time", "date"];
+        for (text, x) in header.iter().zip(SCORE_COLUMNS) {
+            self.write(view, text, SPACE * 5, x, false);
+        }
+        for (rank, entry) in entries.iter().enumerate() {
+            let y = SPACE * (7 + rank as u32);
+            let rank_text = if highlight == Some(rank) {
+                format!(">{}", rank + 1)
+            } else {
+                format!("{}", rank + 1)
+            };
+            let row = [
+                rank_text,
+                entry.name.clone(),
+                mode.headline(entry),
+                entry.level.to_string(),
+                entry.lines.to_string(),
+                entry.time(),
+                entry.day(),
+            ];
+            for (text, x) in row.iter().zip(SCORE_COLUMNS) {
+                self.write(view, text.as_str(), y, x, false);
+            }
+        }
+
+        let y = SPACE * (8 + scores::MAX_ENTRIES as u32);
+        self.write_lines(view, hints, y, 0);
     }
 
     pub fn render_debug(&mut self, view: &wgpu::TextureView, to_dbg: &String) {
-        self.write(&view, &to_dbg.as_str(), SPACE * 20, GAME_AREA_WIDTH, false);
+        self.write(&view, &to_dbg.as_str(), SPACE * 26, GAME_AREA_WIDTH, false);
     }
 
     fn game_arena(&self) -> Geometry {
         let outer_rect = self.rectangle(
-            self.block_size * LEFT_MARGIN - self.line_weight,
+            (self.block_size * self.arena_left).saturating_sub(self.line_weight),
             self.block_size * (BOTTOM_MARGIN + GAME_AREA_HEIGHT) + self.line_weight,
-            self.block_size * (LEFT_MARGIN + GAME_AREA_WIDTH) + self.line_weight,
+            self.block_size * (self.arena_left + GAME_AREA_WIDTH) + self.line_weight,
             self.block_size * BOTTOM_MARGIN - self.line_weight,
             colours::DARK_GREEN,
         );
         let inner_rect = self.rectangle(
-            self.block_size * LEFT_MARGIN,
+            self.block_size * self.arena_left,
             self.block_size * (BOTTOM_MARGIN + GAME_AREA_HEIGHT),
-            self.block_size * (LEFT_MARGIN + GAME_AREA_WIDTH),
+            self.block_size * (self.arena_left + GAME_AREA_WIDTH),
             self.block_size * BOTTOM_MARGIN,
             colours::BLACK,
         );
@@ -166,10 +360,64 @@ impl<'a> Scene {
         outer_rect + inner_rect
     }
 
-    pub fn finish_scene(&mut self, view: &wgpu::TextureView, game_state: &super::GameState) {
-        self.write(view, "FINISHED!", SPACE * 6, SPACE * 5, true);
-        let msg = format!("SCORE  {:?}", game_state.score);
-        self.write(view, msg.as_str(), SPACE * 8, SPACE * 5, false);
+    pub fn finish_scene(
+        &mut self,
+        view: &wgpu::TextureView,
+        game_state: &super::GameState,
+        hints: &[String],
+    ) {
+        let verdict = game_state.mode().verdict(game_state);
+        self.write(view, verdict, SPACE * 6, SPACE * 5, true);
+        // Mode picks the stats, there's always at least one
+        let results = game_state.mode().results(game_state);
+        self.write_lines(view, &results, SPACE * 8, SPACE * 5);
+        let y = SPACE * (9 + results.len() as u32);
+        if let Some(reason) = game_state.game_over {
+            self.write(view, reason.name(), y, SPACE * 5, false);
+        }
+        self.write_lines(view, hints, y + SPACE * 3, SPACE * 5);
+    }
+
+    /// Writes `lines` one under the other.
+    fn write_lines(
+        &mut self,
+        view: &wgpu::TextureView,
+        lines: &[String],
+        y_blocks: u32,
+        x_blocks: u32,
+    ) {
+        for (idx, line) in lines.iter().enumerate() {
+            self.write(view, line.as_str(), y_blocks + idx as u32, x_blocks, false);
+        }
+    }
+
+    /// Draws the `drawable` in one pass, clearing first or drawing over.
+    fn draw(&self, view: &wgpu::TextureView, drawable: &Drawable, load: wgpu::LoadOp<wgpu::Color>) {
+        let mut encoder =
+            self.base
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Geometry command encoder"),
+                });
+
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Geometry render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load, store: true },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            rpass.set_pipeline(&self.pipeline);
+
+            rpass.set_index_buffer(drawable.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            rpass.set_vertex_buffer(0, drawable.vertex_buffer.slice(..));
+            rpass.draw_indexed(0..drawable.index_buffer_len, 0, 0..1);
+        }
+        self.base.queue.submit([encoder.finish()]);
     }
 
     fn write(
@@ -195,7 +443,7 @@ impl<'a> Scene {
 
         let font_size = self.block_size as f32;
         let colour: Color = super::colours::LIGHT_BLUE.into();
-        let pos_x = (LEFT_MARGIN + x_blocks + SPACE) * self.block_size + left_margin;
+        let pos_x = (self.arena_left + x_blocks + SPACE) * self.block_size + left_margin;
         let pos_y = (TOP_MARGIN + y_blocks + SPACE) * self.block_size + top_margin;
         let section = Section::default()
             .add_text(Text::new(text).with_scale(font_size).with_color(colour))
@@ -242,13 +490,13 @@ impl<'a> Scene {
         self.base.queue.submit([encoder.finish(), cmd_buffer]);
     }
 
-    fn calculate_block_size(window_size: &Frame) -> u32 {
+    fn calculate_block_size(window_size: &Frame, columns: u32) -> u32 {
         let block_size: u32 = cmp::min(
             window_size.height / SCREEN_HEIGHT,
-            window_size.width / SCREEN_WIDTH,
+            window_size.width / columns,
         );
 
-        if block_size * SCREEN_WIDTH > window_size.width
+        if block_size * columns > window_size.width
             || block_size * SCREEN_HEIGHT > window_size.height
         {
             if block_size > 5 {
@@ -283,22 +531,86 @@ impl<'a> Scene {
         Geometry { indices, vertices }
     }
 
-    fn next_tetromino_geom(&self, tetromino: &Tetromino) -> Geometry {
+    fn next_tetrominos_geom(&self, next_tetrominos: &VecDeque<CurrentTetromino>) -> Geometry {
         let (ga_left, ga_top) = {
             (
-                self.block_size * (LEFT_MARGIN + GAME_AREA_WIDTH + 3 * SPACE / 2),
+                self.block_size * (self.arena_left + GAME_AREA_WIDTH + 3 * SPACE / 2),
                 self.block_size * (BOTTOM_MARGIN + GAME_AREA_HEIGHT - 3 * SPACE),
             )
         };
 
         let mut blx = Geometry::default();
 
-        let bs = self.block_size;
+        let mut offsy = ga_top;
+        for (idx, next) in next_tetrominos.iter().enumerate() {
+            // Only the very next one is full size, rest of the queue is smaller to fit in the panel
+            let bs = if idx == 0 {
+                self.block_size
+            } else {
+                self.block_size / 2
+            };
+            let (g, height) = self.preview_geom(&next.tetromino, ga_left, offsy, bs);
+            blx += g;
+            offsy -= cmp::min(offsy, height + bs);
+        }
+
+        blx
+    }
+
+    fn held_tetromino_geom(&self, game_state: &super::GameState) -> Geometry {
+        let (hb_left, hb_top) = {
+            (
+                self.block_size * (self.arena_left + SECOND_COLUMN + SPACE),
+                self.block_size * (BOTTOM_MARGIN + GAME_AREA_HEIGHT - 3 * SPACE),
+            )
+        };
+        let (hb_right, hb_bottom) = (
+            hb_left + self.block_size * HOLD_BOX_SIZE,
+            hb_top - self.block_size * HOLD_BOX_SIZE,
+        );
+
+        let border = self.line_weight / 4;
+        let outer_rect = self.rectangle(
+            hb_left - border,
+            hb_top + border,
+            hb_right + border,
+            hb_bottom - border,
+            colours::DARK_GREEN,
+        );
+        let inner_rect = self.rectangle(hb_left, hb_top, hb_right, hb_bottom, colours::BLACK);
+
+        let mut blx = outer_rect + inner_rect;
+
+        if let Some(held) = game_state.held_tetromino {
+            let mut tetromino: Tetromino = held.into();
+            // Dimmed until it can be swapped again
+            if !game_state.can_hold {
+                tetromino.colour = colours::DARK_GREEN;
+            }
+            let half = self.block_size / 2;
+            let (g, _) =
+                self.preview_geom(&tetromino, hb_left + half, hb_top - half, self.block_size);
+            blx += g;
+        }
+
+        blx
+    }
+
+    /// Blocks of the tetromino hanging from the `left`, `top` corner, leaving out the
+    /// empty rows of the bounding box. Returns the geometry with its height.
+    fn preview_geom(&self, tetromino: &Tetromino, left: u32, top: u32, bs: u32) -> (Geometry, u32) {
+        let mut blx = Geometry::default();
+
         let m: u32 = 1;
 
-        let mut offsy = ga_top - bs;
-        for row in &tetromino.shape {
-            let mut offsx = ga_left;
+        let mut height = 0;
+        let rows = tetromino
+            .shape
+            .iter()
+            .filter(|row| row.iter().any(|col| *col != BlockState::Emp));
+        for row in rows {
+            let offsy = top.saturating_sub(height + bs);
+            let mut offsx = left;
             for col in row {
                 let (b_left, b_top, b_right, b_bottom) =
                     { (offsx + m, offsy + m, offsx + bs - m, offsy + bs - m) };
@@ -310,17 +622,17 @@ impl<'a> Scene {
 
                 offsx += bs;
             }
-            offsy -= bs;
+            height += bs;
         }
 
-        blx
+        (blx, height)
     }
 
     fn current_tetromino_geom(&self, current_tetromino: &CurrentTetromino) -> Geometry {
         // Determine the bounding box for the game area
         let (ga_left, ga_top) = {
             (
-                self.block_size * (LEFT_MARGIN),
+                self.block_size * self.arena_left,
                 self.block_size * (BOTTOM_MARGIN + GAME_AREA_HEIGHT),
             )
         };
@@ -332,61 +644,92 @@ impl<'a> Scene {
         let bs = self.block_size;
         let m: u32 = 1;
 
-        // Determining how much of the tetromino should be in view
-        // `current_tetromino.y` is already rebated for the height of the tetromino we first add it back.
-        // Tetrominos start from outside of the "rendered" box, this then gives the amount
-        // of tetromino `in_view`.
-        // Finally we return the index bounds of the rows of the shape that should be rendered.
-        let shape_height = current_tetromino.tetromino.shape.len() as i8;
-        let (in_view_row_start, in_view_row_end, in_view): (usize, usize, usize) = {
-            let in_view = current_tetromino.y + shape_height;
-            if in_view < shape_height {
-                (
-                    (shape_height - in_view) as usize,
-                    shape_height as usize,
-                    in_view as usize,
-                )
-            } else {
-                (0, shape_height as usize, shape_height as usize)
-            }
-        };
+        // Tetrominos start from outside of the "rendered" box, only the rows that
+        // are on the board are drawn.
+        for (x, y) in current_tetromino.cells().filter(|(_, y)| *y >= 0) {
+            let offsx = ga_left + bs * x as u32;
+            let offsy = ga_top - bs * (y as u32 + 1);
+            let (b_left, b_top, b_right, b_bottom) =
+                { (offsx + m, offsy + m, offsx + bs - m, offsy + bs - m) };
+
+            let g = self.rectangle(
+                b_left,
+                b_top,
+                b_right,
+                b_bottom,
+                current_tetromino.tetromino.colour,
+            );
+            blx += g;
+        }
 
-        let dy = in_view as u32;
-        let dh = (current_tetromino.y + shape_height) as u32;
+        blx
+    }
 
-        let mut offsy = ga_top - bs * (dh - dy + 1);
-        for row in &current_tetromino.tetromino.shape[in_view_row_start..in_view_row_end] {
-            let mut offsx = ga_left + bs * (current_tetromino.x as u32);
-            for col in row {
-                let (b_left, b_top, b_right, b_bottom) =
-                    { (offsx + m, offsy + m, offsx + bs - m, offsy + bs - m) };
+    /// Outline of the blocks of the tetromino where it would land.
+    fn ghost_tetromino_geom(&self, ghost_tetromino: &CurrentTetromino) -> Geometry {
+        let (ga_left, ga_top) = {
+            (
+                self.block_size * self.arena_left,
+                self.block_size * (BOTTOM_MARGIN + GAME_AREA_HEIGHT),
+            )
+        };
 
-                if *col != BlockState::Emp {
-                    let g = self.rectangle(
-                        b_left,
-                        b_top,
-                        b_right,
-                        b_bottom,
-                        current_tetromino.tetromino.colour,
-                    );
-                    blx += g;
-                }
+        let mut blx = Geometry::default();
 
-                offsx += bs;
-            }
-            offsy = if offsy > 0 { offsy - bs } else { offsy };
+        let bs = self.block_size;
+        let m: u32 = 1;
+        let border = cmp::max(self.line_weight / 6, 1);
+
+        for (x, y) in ghost_tetromino.cells().filter(|(_, y)| *y >= 0) {
+            let offsx = ga_left + bs * x as u32;
+            let offsy = ga_top - bs * (y as u32 + 1);
+            let (b_left, b_top, b_right, b_bottom) =
+                { (offsx + m, offsy + m, offsx + bs - m, offsy + bs - m) };
+
+            let outer_rect = self.rectangle(
+                b_left,
+                b_top,
+                b_right,
+                b_bottom,
+                ghost_tetromino.tetromino.colour,
+            );
+            let inner_rect = self.rectangle(
+                b_left + border,
+                b_top + border,
+                b_right - border,
+                b_bottom - border,
+                colours::BLACK,
+            );
+            blx += outer_rect + inner_rect;
         }
 
         blx
     }
 
+    /// Bar between the board and the next queue as tall as the garbage about
+    /// to come in.
+    fn garbage_meter_geom(&self, pending: u32) -> Geometry {
+        if pending == 0 {
+            return Geometry::default();
+        }
+        let rows = cmp::min(pending, GAME_AREA_HEIGHT);
+        let left = self.block_size * (self.arena_left + GAME_AREA_WIDTH) + self.line_weight;
+        self.rectangle(
+            left,
+            self.block_size * (BOTTOM_MARGIN + rows),
+            left + cmp::max(self.block_size / 3, 1),
+            self.block_size * BOTTOM_MARGIN,
+            colours::RED,
+        )
+    }
+
     fn blocks(&self, game_state: &super::GameState) -> Geometry {
         let bs = self.block_size;
         let m: u32 = 1;
 
         let (ga_left, ga_top) = {
             (
-                self.block_size * LEFT_MARGIN,
+                self.block_size * self.arena_left,
                 self.block_size * (BOTTOM_MARGIN + GAME_AREA_HEIGHT),
             )
         };
@@ -394,7 +737,7 @@ impl<'a> Scene {
         let mut blx = Geometry::default();
 
         let mut offsy = ga_top - bs;
-        for row in &game_state.blocks[..] {
+        for row in game_state.visible_blocks() {
             let mut offsx = ga_left;
             for col in row {
                 let (b_left, b_top, b_right, b_bottom) =
@@ -410,6 +753,7 @@ impl<'a> Scene {
                         BlockState::Ohh => Tetromino::ohh().colour,
                         BlockState::Tee => Tetromino::tee().colour,
                         BlockState::Zee => Tetromino::zee().colour,
+                        BlockState::Gar => colours::SLATE,
                         _ => colours::UNRENDERED,
                     };
                     let g = self.rectangle(b_left, b_top, b_right, b_bottom, colour);
@@ -0,0 +1,312 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use super::game_state::GameState;
+use super::mode::GameMode;
+
+/// Entries kept for each mode
+pub const MAX_ENTRIES: usize = 10;
+/// Longest name that can be entered
+pub const MAX_NAME: usize = 10;
+
+/// Where the table is kept in the browser's local storage
+#[cfg(target_arch = "wasm32")]
+const STORAGE_KEY: &str = "tetrs.scores";
+
+/// One finished game on the table.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Entry {
+    pub name: String,
+    pub score: u128,
+    pub level: u8,
+    pub lines: u32,
+    pub duration: Duration,
+    pub mode: String,
+    /// Seconds since the Unix epoch
+    pub date: u64,
+}
+
+impl Entry {
+    /// Result of a finished game, without a name yet.
+    pub fn new(game_state: &GameState) -> Entry {
+        Entry {
+            name: String::new(),
+            score: game_state.score,
+            level: game_state.level,
+            lines: game_state.lines,
+            duration: game_state.play_time(),
+            mode: game_state.mode().name(),
+            date: now(),
+        }
+    }
+
+    /// Duration as minutes and seconds.
+    pub fn time(&self) -> String {
+        let secs = self.duration.as_secs();
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+
+    /// Date as day, month and year.
+    pub fn day(&self) -> String {
+        let (year, month, day) = civil_date(self.date / (24 * 60 * 60));
+        format!("{:02}/{:02}/{:02}", day, month, year % 100)
+    }
+}
+
+/// Best results of every mode, best first, along with the name last entered so
+/// it doesn't have to be typed again.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HighScores {
+    pub entries: Vec<Entry>,
+    pub last_name: String,
+}
+
+impl HighScores {
+    /// Entries of a `mode`, best first.
+    pub fn table(&self, mode: &str) -> impl Iterator<Item = &Entry> {
+        let mode = mode.to_string();
+        self.entries.iter().filter(move |entry| entry.mode == mode)
+    }
+
+    /// Whether an `entry` would make it onto the table, ranked the way its
+    /// `mode` ranks them.
+    pub fn qualifies(&self, entry: &Entry, mode: &dyn GameMode) -> bool {
+        let table: Vec<_> = self.table(&entry.mode).collect();
+        table.len() < MAX_ENTRIES || table.iter().any(|other| mode.ranks_above(entry, other))
+    }
+
+    /// Puts an `entry` in its place, dropping the ones that fall off the
+    /// table. Returns its rank if it stayed.
+    pub fn insert(&mut self, entry: Entry, mode: &dyn GameMode) -> Option<usize> {
+        let name = entry.mode.clone();
+        self.last_name = entry.name.clone();
+
+        let at = self
+            .entries
+            .iter()
+            .position(|other| other.mode == name && mode.ranks_above(&entry, other))
+            .unwrap_or(self.entries.len());
+        self.entries.insert(at, entry);
+
+        let mut rank = 0;
+        let mut placed = None;
+        let mut kept = Vec::new();
+        for (idx, entry) in self.entries.drain(..).enumerate() {
+            if entry.mode != name {
+                kept.push(entry);
+                continue;
+            }
+            if rank < MAX_ENTRIES {
+                if idx == at {
+                    placed = Some(rank);
+                }
+                kept.push(entry);
+            }
+            rank += 1;
+        }
+        self.entries = kept;
+        placed
+    }
+
+    /// Table from `scores.json` in the user's data directory, empty if there
+    /// isn't one yet.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load() -> anyhow::Result<HighScores> {
+        let path = HighScores::path()?;
+        if !path.exists() {
+            return Ok(HighScores::default());
+        }
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Couldn't read {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("Couldn't load {}", path.display()))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = HighScores::path()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Couldn't create {}", dir.display()))?;
+        }
+        let text = serde_json::to_string_pretty(self).context("Couldn't write the high scores")?;
+        std::fs::write(&path, text).with_context(|| format!("Couldn't write {}", path.display()))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn path() -> anyhow::Result<std::path::PathBuf> {
+        Ok(dirs::data_dir()
+            .context("Can't find the data directory")?
+            .join("tetrs")
+            .join("scores.json"))
+    }
+
+    /// Table from the browser's local storage, stored as JSON like on native.
+    #[cfg(target_arch = "wasm32")]
+    pub fn load() -> anyhow::Result<HighScores> {
+        match local_storage()?.get_item(STORAGE_KEY).ok().flatten() {
+            Some(text) => serde_json::from_str(&text).context("Couldn't load the high scores"),
+            None => Ok(HighScores::default()),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save(&self) -> anyhow::Result<()> {
+        let text = serde_json::to_string(self).context("Couldn't write the high scores")?;
+        local_storage()?
+            .set_item(STORAGE_KEY, &text)
+            .ok()
+            .context("Couldn't store the high scores")
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> anyhow::Result<web_sys::Storage> {
+    web_sys::window()
+        .and_then(|win| win.local_storage().ok().flatten())
+        .context("Can't access the local storage")
+}
+
+/// Seconds since the Unix epoch.
+#[cfg(not(target_arch = "wasm32"))]
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn now() -> u64 {
+    (js_sys::Date::now() / 1000.0) as u64
+}
+
+/// Year, month and day of the `days` since the Unix epoch, in the proleptic
+/// Gregorian calendar.
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    // Shifted to start from 0000-03-01 so leap days come at the end of a year
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mode::{Endless, Sprint};
+    use super::*;
+
+    fn entry(name: &str, mode: &str, score: u128, secs: u64) -> Entry {
+        Entry {
+            name: name.to_string(),
+            score,
+            level: 1,
+            lines: 0,
+            duration: Duration::from_secs(secs),
+            mode: mode.to_string(),
+            date: 0,
+        }
+    }
+
+    fn names<'a>(high_scores: &'a HighScores, mode: &str) -> Vec<&'a str> {
+        high_scores
+            .table(mode)
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn inserted_in_place() {
+        let mut high_scores = HighScores::default();
+        assert_eq!(
+            high_scores.insert(entry("B", "endless", 200, 0), &Endless),
+            Some(0)
+        );
+        assert_eq!(
+            high_scores.insert(entry("C", "endless", 100, 0), &Endless),
+            Some(1)
+        );
+        assert_eq!(
+            high_scores.insert(entry("A", "endless", 300, 0), &Endless),
+            Some(0)
+        );
+        assert_eq!(names(&high_scores, "endless"), ["A", "B", "C"]);
+        assert_eq!(high_scores.last_name, "A");
+    }
+
+    #[test]
+    fn full_table_keeps_the_best() {
+        let mut high_scores = HighScores::default();
+        for score in 1..=MAX_ENTRIES as u128 {
+            let entry = entry(&score.to_string(), "endless", score * 100, 0);
+            assert!(high_scores.qualifies(&entry, &Endless));
+            high_scores.insert(entry, &Endless);
+        }
+
+        let low = entry("low", "endless", 50, 0);
+        assert!(!high_scores.qualifies(&low, &Endless));
+        assert_eq!(high_scores.insert(low, &Endless), None);
+        assert_eq!(high_scores.table("endless").count(), MAX_ENTRIES);
+
+        let high = entry("high", "endless", 550, 0);
+        assert!(high_scores.qualifies(&high, &Endless));
+        assert_eq!(high_scores.insert(high, &Endless), Some(5));
+        assert_eq!(high_scores.table("endless").count(), MAX_ENTRIES);
+        // The lowest fell off
+        assert!(high_scores.table("endless").all(|entry| entry.score > 100));
+    }
+
+    #[test]
+    fn every_mode_has_a_table() {
+        let mut high_scores = HighScores::default();
+        for score in 1..=MAX_ENTRIES as u128 {
+            high_scores.insert(entry("endless", "endless", score * 100, 0), &Endless);
+        }
+        // A full table of another mode doesn't push anything off
+        let sprint = entry("sprint", "sprint", 0, 90);
+        assert!(high_scores.qualifies(&sprint, &Sprint));
+        assert_eq!(high_scores.insert(sprint, &Sprint), Some(0));
+        assert_eq!(high_scores.table("endless").count(), MAX_ENTRIES);
+        assert_eq!(names(&high_scores, "sprint"), ["sprint"]);
+    }
+
+    #[test]
+    fn sprint_ties_go_below() {
+        let mut high_scores = HighScores::default();
+        high_scores.insert(entry("slow", "sprint", 0, 120), &Sprint);
+        high_scores.insert(entry("fast", "sprint", 0, 60), &Sprint);
+        assert_eq!(
+            high_scores.insert(entry("tie", "sprint", 0, 60), &Sprint),
+            Some(1)
+        );
+        assert_eq!(names(&high_scores, "sprint"), ["fast", "tie", "slow"]);
+
+        for _ in 3..MAX_ENTRIES {
+            high_scores.insert(entry("slow", "sprint", 0, 120), &Sprint);
+        }
+        // Tying the slowest of a full table isn't enough
+        let tie = entry("tie", "sprint", 0, 120);
+        assert!(!high_scores.qualifies(&tie, &Sprint));
+        assert_eq!(high_scores.insert(tie, &Sprint), None);
+    }
+
+    #[test]
+    fn civil_dates() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(11_016), (2000, 2, 29));
+        assert_eq!(civil_date(11_017), (2000, 3, 1));
+        assert_eq!(civil_date(20_744), (2026, 10, 18));
+    }
+}
@@ -0,0 +1,250 @@
+use serde::{Deserialize, Serialize};
+
+/// Kind of spin the tetromino was locked with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+/// What a single lock did on the board.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Clear {
+    pub lines: u8,
+    pub spin: Spin,
+}
+
+impl Clear {
+    /// Clears that keep the back-to-back chain going.
+    pub fn is_difficult(&self) -> bool {
+        self.lines >= 4 || (self.lines > 0 && self.spin != Spin::None)
+    }
+
+    pub fn name(&self) -> String {
+        let spin = match self.spin {
+            Spin::None => None,
+            Spin::Mini => Some("mini t-spin"),
+            Spin::Full => Some("t-spin"),
+        };
+        let lines = match self.lines {
+            0 => None,
+            1 => Some("single"),
+            2 => Some("double"),
+            3 => Some("triple"),
+            _ => Some("tetris"),
+        };
+        spin.into_iter().chain(lines).collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// Turns what happens in the game into points and levels.
+pub trait ScoringSystem {
+    /// Points for a lock. `combo` is the number of line clearing locks in a row,
+    /// including this one, `back_to_back` is set when this clear continues a
+    /// chain of difficult ones.
+    fn clear(&self, clear: &Clear, level: u8, combo: u32, back_to_back: bool) -> u128;
+    fn soft_drop(&self, cells: u32) -> u128;
+    fn hard_drop(&self, cells: u32) -> u128;
+    /// Level after clearing `lines` in total
+    fn level(&self, lines: u32) -> u8;
+}
+
+/// Available scoring systems.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoringStyle {
+    /// Guideline scoring with combos, back-to-backs and T-spins
+    Guideline,
+    /// A point per line
+    Simple,
+}
+
+impl std::default::Default for ScoringStyle {
+    fn default() -> Self {
+        ScoringStyle::Guideline
+    }
+}
+
+impl std::str::FromStr for ScoringStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "guideline" => Ok(ScoringStyle::Guideline),
+            "simple" => Ok(ScoringStyle::Simple),
+            _ => Err(anyhow::Error::msg(format!("Unknown scoring {}", s))),
+        }
+    }
+}
+
+impl ScoringStyle {
+    pub fn system(&self) -> Box<dyn ScoringSystem> {
+        match self {
+            ScoringStyle::Guideline => Box::new(Guideline),
+            ScoringStyle::Simple => Box::new(Simple),
+        }
+    }
+}
+
+/// Guideline scoring, everything is multiplied by the level which goes up every
+/// 10 lines.
+pub struct Guideline;
+
+impl Guideline {
+    const LINES_PER_LEVEL: u32 = 10;
+    const COMBO: u128 = 50;
+    const SOFT_DROP: u128 = 1;
+    const HARD_DROP: u128 = 2;
+}
+
+impl ScoringSystem for Guideline {
+    fn clear(&self, clear: &Clear, level: u8, combo: u32, back_to_back: bool) -> u128 {
+        let base: u128 = match (clear.spin, clear.lines) {
+            (Spin::None, 0) => 0,
+            (Spin::None, 1) => 100,
+            (Spin::None, 2) => 300,
+            (Spin::None, 3) => 500,
+            (Spin::None, _) => 800,
+            (Spin::Mini, 0) => 100,
+            (Spin::Mini, 1) => 200,
+            (Spin::Mini, _) => 400,
+            (Spin::Full, 0) => 400,
+            (Spin::Full, 1) => 800,
+            (Spin::Full, 2) => 1200,
+            (Spin::Full, _) => 1600,
+        };
+        let level = level as u128;
+
+        let mut points = base * level;
+        if back_to_back {
+            points = points * 3 / 2;
+        }
+        if combo > 1 {
+            points += Guideline::COMBO * (combo - 1) as u128 * level;
+        }
+        points
+    }
+
+    fn soft_drop(&self, cells: u32) -> u128 {
+        Guideline::SOFT_DROP * cells as u128
+    }
+
+    fn hard_drop(&self, cells: u32) -> u128 {
+        Guideline::HARD_DROP * cells as u128
+    }
+
+    fn level(&self, lines: u32) -> u8 {
+        clamp_level(lines / Guideline::LINES_PER_LEVEL + 1)
+    }
+}
+
+/// The original scoring, a point per line cleared and a level every 20 lines.
+pub struct Simple;
+
+impl Simple {
+    const LINES_PER_LEVEL: u32 = 20;
+}
+
+impl ScoringSystem for Simple {
+    fn clear(&self, clear: &Clear, _level: u8, _combo: u32, _back_to_back: bool) -> u128 {
+        clear.lines as u128
+    }
+
+    fn soft_drop(&self, _cells: u32) -> u128 {
+        0
+    }
+
+    fn hard_drop(&self, _cells: u32) -> u128 {
+        0
+    }
+
+    fn level(&self, lines: u32) -> u8 {
+        clamp_level(lines / Simple::LINES_PER_LEVEL)
+    }
+}
+
+fn clamp_level(level: u32) -> u8 {
+    level.min(u8::MAX as u32) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tee_spin_double_beats_mini() {
+        let full = Clear {
+            lines: 2,
+            spin: Spin::Full,
+        };
+        let mini = Clear {
+            lines: 2,
+            spin: Spin::Mini,
+        };
+        assert_eq!(Guideline.clear(&full, 1, 1, false), 1200);
+        assert_eq!(Guideline.clear(&mini, 1, 1, false), 400);
+        assert_eq!(Guideline.clear(&full, 3, 1, false), 3600);
+    }
+
+    #[test]
+    fn clear_names() {
+        assert_eq!(
+            Clear {
+                lines: 4,
+                spin: Spin::None
+            }
+            .name(),
+            "tetris"
+        );
+        assert_eq!(
+            Clear {
+                lines: 2,
+                spin: Spin::Full
+            }
+            .name(),
+            "t-spin double"
+        );
+        assert_eq!(
+            Clear {
+                lines: 1,
+                spin: Spin::Mini
+            }
+            .name(),
+            "mini t-spin single"
+        );
+        assert_eq!(
+            Clear {
+                lines: 0,
+                spin: Spin::Full
+            }
+            .name(),
+            "t-spin"
+        );
+        assert_eq!(
+            Clear {
+                lines: 0,
+                spin: Spin::Mini
+            }
+            .name(),
+            "mini t-spin"
+        );
+    }
+
+    #[test]
+    fn combo_adds_per_clear() {
+        let single = Clear {
+            lines: 1,
+            spin: Spin::None,
+        };
+        assert_eq!(Guideline.clear(&single, 2, 1, false), 200);
+        assert_eq!(Guideline.clear(&single, 2, 2, false), 200 + 100);
+        assert_eq!(Guideline.clear(&single, 2, 4, false), 200 + 300);
+        // Nothing cleared ends the combo, it's worth nothing by itself
+        let none = Clear {
+            lines: 0,
+            spin: Spin::None,
+        };
+        assert_eq!(Guideline.clear(&none, 2, 0, false), 0);
+    }
+}
@@ -1,4 +1,5 @@
 use super::game_state::NUM_COLS;
+use super::rotation::{Orientation, Rotation};
 use super::{colours, colours::Colour};
 
 #[derive(Clone, Copy, PartialEq)]
@@ -11,6 +12,8 @@ pub enum BlockState {
     Ohh,
     Tee,
     Zee,
+    /// Garbage pushed up from the bottom, not part of any tetromino
+    Gar,
 }
 
 impl Into<Tetromino> for BlockState {
@@ -23,12 +26,12 @@ impl Into<Tetromino> for BlockState {
             BlockState::Ohh => Tetromino::ohh(),
             BlockState::Tee => Tetromino::tee(),
             BlockState::Zee => Tetromino::zee(),
-            BlockState::Emp => Tetromino::emp(),
+            BlockState::Emp | BlockState::Gar => Tetromino::emp(),
         }
     }
 }
 
-const NEXT_TETRO_BAG: [BlockState; 7] = [
+pub const NEXT_TETRO_BAG: [BlockState; 7] = [
     BlockState::Arr,
     BlockState::Ell,
     BlockState::Ess,
@@ -41,17 +44,11 @@ const NEXT_TETRO_BAG: [BlockState; 7] = [
 #[derive(Clone)]
 pub struct CurrentTetromino {
     pub tetromino: Tetromino,
-    pub x: usize,
+    pub x: i8,
     pub y: i8,
 }
 
 impl CurrentTetromino {
-    pub fn next_one() -> CurrentTetromino {
-        let next_idx: usize = random_number::random!(..NEXT_TETRO_BAG.len());
-        let tetro: Tetromino = NEXT_TETRO_BAG[next_idx].into();
-        tetro.into()
-    }
-
     pub fn down(&mut self) {
         self.y += 1;
     }
@@ -61,85 +58,135 @@ impl CurrentTetromino {
     }
 
     pub fn left(&mut self) {
-        let newx = self.x as i8 - 1;
-        self.x = if newx >= 0 { newx as usize } else { 0 };
+        self.x -= 1;
     }
 
-    pub fn rotate(&mut self) {
-        self.tetromino.rotate();
+    pub fn rotate(&mut self, rotation: Rotation) {
+        self.tetromino.rotate(rotation);
+    }
+
+    /// Board coordinates of the filled blocks of the tetromino, `(x, y)`.
+    pub fn cells(&self) -> impl Iterator<Item = (i8, i8)> + '_ {
+        self.tetromino
+            .shape
+            .iter()
+            .enumerate()
+            .flat_map(move |(row_idx, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, col)| **col != BlockState::Emp)
+                    .map(move |(col_idx, _)| (self.x + col_idx as i8, self.y + row_idx as i8))
+            })
     }
 }
 
 #[derive(Clone)]
 pub struct Tetromino {
+    /// Which one of the seven this is
+    pub kind: BlockState,
     /// Colour associated with blocks of this tetromino
     pub colour: Colour,
     /// A bounding box of blocks that has the shape filled in with coloured blocks
     pub shape: Vec<Vec<BlockState>>,
-    /// Padding from the top of the board so that bottom of the tetromino is just
-    /// above the visible part. Basically `- tetromino_height`.
-    pub starting_pos_y: i8,
-    /// Starting positin in the horizontal.
-    /// Basically `(NUM_COLS - tetromino_width) / 2`.
-    pub starting_pos_x: usize,
+    /// Which way the shape is facing
+    pub orientation: Orientation,
+}
+
+impl Into<CurrentTetromino> for BlockState {
+    fn into(self) -> CurrentTetromino {
+        let tetro: Tetromino = self.into();
+        tetro.into()
+    }
 }
 
 impl Into<CurrentTetromino> for Tetromino {
     fn into(self) -> CurrentTetromino {
+        let (x, y) = self.spawn_position();
         CurrentTetromino {
-            x: self.starting_pos_x,
-            y: self.starting_pos_y,
+            x,
+            y,
             tetromino: self,
         }
     }
 }
 
 impl Tetromino {
-    /// A CCW rotation of the tetromino
-    pub fn rotate(&mut self) {
+    /// Rotates the bounding box of the tetromino
+    pub fn rotate(&mut self, rotation: Rotation) {
         let (height, width) = (self.shape.len(), self.shape[0].len());
-        let mut new_shape = vec![vec![BlockState::Emp; height]; width];
-        for (row_idx, row) in self.shape.iter().enumerate() {
-            for (col_idx, col) in row.iter().enumerate() {
-                new_shape[width - col_idx - 1][row_idx] = *col;
+        self.shape = match rotation {
+            Rotation::Clockwise => {
+                let mut new_shape = vec![vec![BlockState::Emp; height]; width];
+                for (row_idx, row) in self.shape.iter().enumerate() {
+                    for (col_idx, col) in row.iter().enumerate() {
+                        new_shape[col_idx][height - row_idx - 1] = *col;
+                    }
+                }
+                new_shape
             }
-        }
-        self.shape = new_shape;
+            Rotation::CounterClockwise => {
+                let mut new_shape = vec![vec![BlockState::Emp; height]; width];
+                for (row_idx, row) in self.shape.iter().enumerate() {
+                    for (col_idx, col) in row.iter().enumerate() {
+                        new_shape[width - col_idx - 1][row_idx] = *col;
+                    }
+                }
+                new_shape
+            }
+            Rotation::Half => self
+                .shape
+                .iter()
+                .rev()
+                .map(|row| row.iter().rev().copied().collect())
+                .collect(),
+        };
+        self.orientation = self.orientation.rotated(rotation);
+    }
+
+    /// Where the bounding box is placed on the board when the tetromino comes in.
+    /// Horizontally it's centered, rounding to the left, and vertically the lowest
+    /// filled row is just above the visible part.
+    pub fn spawn_position(&self) -> (i8, i8) {
+        let width = self.shape[0].len() as i8;
+        let filled_rows = self
+            .shape
+            .iter()
+            .rposition(|row| row.iter().any(|col| *col != BlockState::Emp))
+            .map_or(0, |row_idx| row_idx as i8 + 1);
+        ((NUM_COLS as i8 - width) / 2, -filled_rows)
     }
 
     ///
-    /// xx
-    /// x
     /// x
+    /// xxx
     ///
     pub fn arr() -> Self {
         Tetromino {
+            kind: BlockState::Arr,
             colour: colours::RED,
             shape: vec![
-                vec![BlockState::Arr, BlockState::Arr],
-                vec![BlockState::Arr, BlockState::Emp],
-                vec![BlockState::Arr, BlockState::Emp],
+                vec![BlockState::Arr, BlockState::Emp, BlockState::Emp],
+                vec![BlockState::Arr, BlockState::Arr, BlockState::Arr],
+                vec![BlockState::Emp, BlockState::Emp, BlockState::Emp],
             ],
-            starting_pos_y: -3,
-            starting_pos_x: (NUM_COLS - 2) / 2,
+            orientation: Orientation::Spawn,
         }
     }
 
     ///
-    /// x
-    /// x
-    /// xx
+    ///   x
+    /// xxx
     ///
     pub fn ell() -> Self {
         Tetromino {
+            kind: BlockState::Ell,
             colour: colours::BROWN,
             shape: vec![
-                vec![BlockState::Ell, BlockState::Emp],
-                vec![BlockState::Ell, BlockState::Emp],
-                vec![BlockState::Ell, BlockState::Ell],
+                vec![BlockState::Emp, BlockState::Emp, BlockState::Ell],
+                vec![BlockState::Ell, BlockState::Ell, BlockState::Ell],
+                vec![BlockState::Emp, BlockState::Emp, BlockState::Emp],
             ],
-            starting_pos_y: -3,
-            starting_pos_x: (NUM_COLS - 2) / 2,
+            orientation: Orientation::Spawn,
         }
     }
 
@@ -149,33 +196,33 @@ impl Tetromino {
     ///
     pub fn ess() -> Self {
         Tetromino {
+            kind: BlockState::Ess,
             colour: colours::MAROON,
             shape: vec![
                 vec![BlockState::Emp, BlockState::Ess, BlockState::Ess],
                 vec![BlockState::Ess, BlockState::Ess, BlockState::Emp],
+                vec![BlockState::Emp, BlockState::Emp, BlockState::Emp],
             ],
-            starting_pos_y: -2,
-            starting_pos_x: (NUM_COLS - 3) / 2,
+            orientation: Orientation::Spawn,
         }
     }
 
     ///
-    /// x
-    /// x
-    /// x
-    /// x
+    ///
+    /// xxxx
+    ///
     ///
     pub fn eye() -> Self {
         Tetromino {
+            kind: BlockState::Eye,
             colour: colours::LIGHT_PURPLE,
             shape: vec![
-                vec![BlockState::Eye],
-                vec![BlockState::Eye],
-                vec![BlockState::Eye],
-                vec![BlockState::Eye],
+                vec![BlockState::Emp; 4],
+                vec![BlockState::Eye; 4],
+                vec![BlockState::Emp; 4],
+                vec![BlockState::Emp; 4],
             ],
-            starting_pos_y: -4,
-            starting_pos_x: (NUM_COLS - 1) / 2,
+            orientation: Orientation::Spawn,
         }
     }
 
@@ -185,13 +232,13 @@ impl Tetromino {
     ///
     pub fn ohh() -> Self {
         Tetromino {
+            kind: BlockState::Ohh,
             colour: colours::NAVY_BLUE,
             shape: vec![
                 vec![BlockState::Ohh, BlockState::Ohh],
                 vec![BlockState::Ohh, BlockState::Ohh],
             ],
-            starting_pos_y: -2,
-            starting_pos_x: (NUM_COLS - 2) / 2,
+            orientation: Orientation::Spawn,
         }
     }
 
@@ -201,13 +248,14 @@ impl Tetromino {
     ///
     pub fn tee() -> Self {
         Tetromino {
+            kind: BlockState::Tee,
             colour: colours::GRAY,
             shape: vec![
                 vec![BlockState::Emp, BlockState::Tee, BlockState::Emp],
                 vec![BlockState::Tee, BlockState::Tee, BlockState::Tee],
+                vec![BlockState::Emp, BlockState::Emp, BlockState::Emp],
             ],
-            starting_pos_y: -2,
-            starting_pos_x: (NUM_COLS - 3) / 2,
+            orientation: Orientation::Spawn,
         }
     }
 
@@ -217,13 +265,14 @@ impl Tetromino {
     ///
     pub fn zee() -> Self {
         Tetromino {
+            kind: BlockState::Zee,
             colour: colours::GREEN,
             shape: vec![
                 vec![BlockState::Zee, BlockState::Zee, BlockState::Emp],
                 vec![BlockState::Emp, BlockState::Zee, BlockState::Zee],
+                vec![BlockState::Emp, BlockState::Emp, BlockState::Emp],
             ],
-            starting_pos_y: -2,
-            starting_pos_x: (NUM_COLS - 3) / 2,
+            orientation: Orientation::Spawn,
         }
     }
 
@@ -233,10 +282,10 @@ impl Tetromino {
     ///
     pub fn emp() -> Self {
         Tetromino {
+            kind: BlockState::Emp,
             colour: colours::GREEN,
             shape: vec![vec![]],
-            starting_pos_y: 0,
-            starting_pos_x: 0,
+            orientation: Orientation::Spawn,
         }
     }
 }
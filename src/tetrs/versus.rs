@@ -0,0 +1,49 @@
+use std::collections::HashSet;
+
+use winit::event::ElementState;
+
+use super::controls::Action;
+use super::game_state::{GameState, Outcome};
+use super::rules::Rules;
+
+/// Second player of a local versus game, playing a board of their own next to
+/// the first player's.
+pub struct Opponent {
+    pub board: GameState,
+    /// Actions whose keys are held down, to tell presses from OS key repeats
+    held_actions: HashSet<Action>,
+}
+
+impl Opponent {
+    /// Board with a seed of its own, so the two players get different pieces.
+    pub fn new(rules: Rules) -> Opponent {
+        let board = GameState::new(random_number::random!(), rules);
+        log::info!("Player two starts with seed {}", board.seed);
+        Opponent {
+            board,
+            held_actions: HashSet::new(),
+        }
+    }
+
+    /// Key of an `action` going down or up, same as for the first player.
+    pub fn handle_action(&mut self, action: Action, state: ElementState) -> Vec<Outcome> {
+        match state {
+            ElementState::Pressed if self.held_actions.insert(action) => self.board.press(action),
+            ElementState::Pressed => Vec::new(),
+            ElementState::Released => {
+                self.held_actions.remove(&action);
+                self.board.release(action);
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Which of the two players won a versus game, the one still standing.
+pub fn winner(player_one: &GameState) -> &'static str {
+    if player_one.is_finished() {
+        "PLAYER 2 WINS!"
+    } else {
+        "PLAYER 1 WINS!"
+    }
+}
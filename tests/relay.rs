@@ -0,0 +1,169 @@
+use std::collections::VecDeque;
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+
+use tet_rs::tetrs::controls::Action;
+use tet_rs::tetrs::game_state::GameState;
+use tet_rs::tetrs::mode::Mode;
+use tet_rs::tetrs::online::{Connection, Mirror};
+use tet_rs::tetrs::protocol::{Garbage, Message};
+use tet_rs::tetrs::replay::Input;
+use tet_rs::tetrs::rules::Rules;
+use tet_rs::tetrs::TICK;
+
+/// How long a message may take to come through the relay
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Client of a relay, keeping what came in until it's asked for.
+struct Client {
+    connection: Connection,
+    inbox: VecDeque<Message>,
+}
+
+impl Client {
+    fn join(url: &str, rules: &Rules) -> Client {
+        let mut client = Client {
+            connection: Connection::open(url).unwrap(),
+            inbox: VecDeque::new(),
+        };
+        client.send(&Message::Join {
+            room: "test".to_string(),
+            rules: rules.clone(),
+        });
+        client
+    }
+
+    /// Sends a message, polling to get it out if the socket was busy.
+    fn send(&mut self, message: &Message) {
+        self.connection.send(message).unwrap();
+        let messages = self.connection.poll().unwrap();
+        self.inbox.extend(messages);
+    }
+
+    fn next(&mut self) -> Message {
+        let deadline = Instant::now() + TIMEOUT;
+        while self.inbox.is_empty() {
+            assert!(Instant::now() < deadline, "Nothing came from the relay");
+            let messages = self.connection.poll().unwrap();
+            self.inbox.extend(messages);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        self.inbox.pop_front().unwrap()
+    }
+}
+
+/// Relay on a free port of its own, returns its url.
+fn relay() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    std::thread::spawn(move || tet_rs::tetrs::relay::serve(listener));
+    format!("ws://{}", address)
+}
+
+/// Keys pressed and let go at a `tick`. Every 16 ticks a tetromino is moved
+/// somewhere else across the board, sometimes turned, and dropped.
+fn script(tick: u64) -> Vec<(Action, bool)> {
+    let piece = tick / 16;
+    // From four columns to the left to four to the right
+    let offset = (piece % 9) as i64 - 4;
+    let action = match tick % 16 {
+        step @ 0..=7 if step / 2 < offset.unsigned_abs() && offset < 0 => Action::MoveLeft,
+        step @ 0..=7 if step / 2 < offset.unsigned_abs() => Action::MoveRight,
+        8..=9 if piece % 3 == 1 => Action::RotateClockwise,
+        12..=13 => Action::HardDrop,
+        _ => return Vec::new(),
+    };
+    // Keys go down on even ticks and are let go on the next one
+    let released = tick % 2 == 1;
+    vec![(action, !released)]
+}
+
+#[test]
+fn relay_pairs_and_passes_on_the_game() {
+    let url = relay();
+    let rules = Rules {
+        mode: Mode::Versus,
+        ..Rules::default()
+    };
+
+    let mut one = Client::join(&url, &rules);
+    assert!(matches!(one.next(), Message::Waiting));
+    let mut two = Client::join(&url, &rules);
+    let (seed, opponent_seed) = match one.next() {
+        Message::Start {
+            seed,
+            opponent_seed,
+            ..
+        } => (seed, opponent_seed),
+        message => panic!("Player one got {:?}", message),
+    };
+    match two.next() {
+        Message::Start {
+            seed: two_seed,
+            opponent_seed: two_opponent_seed,
+            ..
+        } => {
+            assert_eq!(two_seed, opponent_seed);
+            assert_eq!(two_opponent_seed, seed);
+        }
+        message => panic!("Player two got {:?}", message),
+    }
+
+    // Player one plays, player two runs a mirror of it from the frames
+    let mut board = GameState::new(seed, rules.clone());
+    let mut mirror = Mirror::new(seed, rules);
+    let (mut inputs, mut garbage) = (Vec::new(), Vec::new());
+    for tick in 0..320 {
+        for (action, pressed) in script(tick) {
+            inputs.push(Input(tick, action, pressed));
+            if pressed {
+                board.press(action);
+            } else {
+                board.release(action);
+            }
+        }
+        if tick == 100 {
+            board.receive(3);
+            garbage.push(Garbage(tick, 3));
+        }
+        board.step_time(TICK);
+        if tick % 10 == 9 {
+            one.send(&Message::Frame {
+                tick: board.steps_elapsed as u64,
+                inputs: std::mem::take(&mut inputs),
+                garbage: std::mem::take(&mut garbage),
+            });
+        }
+    }
+    assert!(board.garbage_added > 0);
+    assert!(!board.is_finished());
+
+    let last = board.steps_elapsed as u64;
+    loop {
+        match two.next() {
+            Message::Frame {
+                tick,
+                inputs,
+                garbage,
+            } => {
+                mirror.frame(tick, inputs, garbage);
+                if tick == last {
+                    break;
+                }
+            }
+            message => panic!("Player two got {:?}", message),
+        }
+    }
+    assert_eq!(mirror.board.steps_elapsed, board.steps_elapsed);
+    assert_eq!(mirror.board.score, board.score);
+    assert_eq!(mirror.board.pieces, board.pieces);
+    assert!(mirror.board.blocks == board.blocks);
+
+    one.send(&Message::Attack { rows: 2 });
+    assert!(matches!(two.next(), Message::Attack { rows: 2 }));
+    one.send(&Message::GameOver);
+    assert!(matches!(two.next(), Message::GameOver));
+
+    drop(one);
+    assert!(matches!(two.next(), Message::Left));
+}